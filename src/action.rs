@@ -1,13 +1,25 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::sync::Arc;
 
 use anyhow::Result;
+use parquet::arrow::{arrow_to_parquet_schema, parquet_to_arrow_schema};
+use parquet::schema::types::SchemaDescriptor;
 use thiserror::Error;
 
 use crate::base::{Bytes, Format, ObjectKey, Partition};
+use crate::compress::Compression;
+use crate::generate::Generator;
+use crate::drift::{Drift, DriftError};
+use crate::effect::{Effect, EffectIndex};
 use crate::path::{DatasetPath, ObjectPath, PartitionPath};
-use crate::state::{DatasetState, ObjectState, PartitionState, State, StateError};
-use crate::store::{RebalanceTarget, Store, StoreError};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
+use crate::state::{
+    CsvFormatState, DatasetState, FormatState, ObjectState, ParquetFormatState, PartitionState,
+    State, StateError,
+};
+use crate::store::{common_format, RebalanceTarget, Store, StoreError};
 
 #[derive(Error, Debug)]
 pub enum ActionError {
@@ -18,21 +30,74 @@ pub enum ActionError {
     Store(#[from] StoreError),
 }
 
-pub trait Action: fmt::Debug {
+pub trait Action: fmt::Debug + Send + Sync {
     fn key(&self) -> String;
+    fn effects(&self) -> Vec<Effect>;
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State>;
+
+    /// Predicts the state after the action without touching the store.
+    fn apply(&self, state: &State) -> Result<State>;
+
+    /// Predicts the state after the action for a plan, without writing to the store. Actions
+    /// that only read the store, like reloads, can read it rather than predict.
+    fn predict(&self, _store: &dyn Store, state: &State) -> Result<State> {
+        self.apply(state)
+    }
+
+    /// Actions undoing this one, given the state before it runs, in the order they must run.
+    /// None if the action cannot be undone, e.g. when it overwrites or removes an object.
+    fn inverse(&self, state: &State) -> Option<Actions>;
+
+    /// Splits a move into a copy and a removal of the source, for stores without native moves.
+    fn split_move(&self) -> Option<(Box<dyn Action>, Box<dyn Action>)> {
+        None
+    }
 }
 
 pub type Actions = Vec<Box<dyn Action>>;
 
+/// Removal of the partition of `path`, undoing its creation if it is not in `state`.
+fn remove_created_partition(state: &State, path: &ObjectPath) -> Option<Box<dyn Action>> {
+    let partition = path.partition_path();
+
+    match state.contains_partition(partition) {
+        true => None,
+        false => Some(Box::new(RemovePartitionAction::new(partition.clone()))),
+    }
+}
+
+/// Removal of an object written by an action along with the partition it created, if any.
+/// None if the object is in `state`, overwriting it cannot be undone.
+fn remove_created_object(state: &State, path: &ObjectPath) -> Option<Actions> {
+    if state.contains_object(path) {
+        return None;
+    }
+
+    let mut inverse: Actions = vec![Box::new(RemoveObjectAction::new(path.clone()))];
+    inverse.extend(remove_created_partition(state, path));
+    Some(inverse)
+}
+
 #[derive(Clone, Debug)]
 pub struct ReloadDatasetAction {
     path: DatasetPath,
+    allow_missing: bool,
 }
 
 impl ReloadDatasetAction {
     pub fn new(path: DatasetPath) -> Self {
-        Self { path }
+        Self {
+            path,
+            allow_missing: false,
+        }
+    }
+
+    /// Reloads a dataset that may not exist yet in the store, loading it as empty if so.
+    pub fn new_optional(path: DatasetPath) -> Self {
+        Self {
+            path,
+            allow_missing: true,
+        }
     }
 
     fn load_dataset(&self, store: &dyn Store) -> Result<DatasetState> {
@@ -54,8 +119,80 @@ impl Action for ReloadDatasetAction {
         format!("reload({})", self.path)
     }
 
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::update(self.path.clone())]
+    }
+
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
-        Ok(state.insert_dataset(&self.path, self.load_dataset(store)?)?)
+        let dataset = match self.load_dataset(store) {
+            Err(error)
+                if self.allow_missing
+                    && matches!(
+                        error.downcast_ref::<StoreError>(),
+                        Some(StoreError::MissingDataset(_))
+                    ) =>
+            {
+                DatasetState::default()
+            }
+            result => result?,
+        };
+
+        state.insert_dataset(&self.path, dataset)
+    }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        Ok(state.clone())
+    }
+
+    fn predict(&self, store: &dyn Store, state: &State) -> Result<State> {
+        self.execute(store, state)
+    }
+
+    fn inverse(&self, _: &State) -> Option<Actions> {
+        Some(vec![])
+    }
+}
+
+/// Loads a dataset from the store and fails if it differs from the dataset in the state.
+#[derive(Clone, Debug)]
+pub struct VerifyDatasetAction {
+    path: DatasetPath,
+}
+
+impl VerifyDatasetAction {
+    pub fn new(path: DatasetPath) -> Self {
+        Self { path }
+    }
+}
+
+impl Action for VerifyDatasetAction {
+    fn key(&self) -> String {
+        format!("verify({})", self.path)
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::read(self.path.clone())]
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        // A dataset missing from the store is loaded empty, its partitions are all missing
+        let reload = ReloadDatasetAction::new_optional(self.path.clone());
+        let actual = reload.execute(store, &State::new())?;
+
+        let drift = Drift::between(&self.path, state, &actual)?;
+        if !drift.is_empty() {
+            return Err(DriftError::Drifted(self.path.clone(), drift).into());
+        }
+
+        self.apply(state)
+    }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        Ok(state.clone())
+    }
+
+    fn inverse(&self, _: &State) -> Option<Actions> {
+        Some(vec![])
     }
 }
 
@@ -89,8 +226,64 @@ impl Action for ReloadPartitionAction {
         format!("reload({})", self.path)
     }
 
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::update(self.path.clone())]
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        state.insert_partition(&self.path, self.load_partition(store)?)
+    }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        Ok(state.clone())
+    }
+
+    fn predict(&self, store: &dyn Store, state: &State) -> Result<State> {
+        self.execute(store, state)
+    }
+
+    fn inverse(&self, _: &State) -> Option<Actions> {
+        Some(vec![])
+    }
+}
+
+#[derive(Debug)]
+pub struct RemoveDatasetAction {
+    path: DatasetPath,
+}
+
+impl RemoveDatasetAction {
+    pub fn new(path: DatasetPath) -> Self {
+        Self { path }
+    }
+}
+
+impl Action for RemoveDatasetAction {
+    fn key(&self) -> String {
+        format!("rm({}/)", self.path)
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::remove(self.path.clone())]
+    }
+
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
-        Ok(state.insert_partition(&self.path, self.load_partition(store)?)?)
+        let new_state = self.apply(state)?;
+        store.remove_dataset(&self.path)?;
+
+        Ok(new_state)
+    }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        state.remove_dataset(&self.path)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        // Datasets are created again by the objects moved or copied back into them
+        match state.list_partitions(&self.path) {
+            Ok(partitions) if partitions.is_empty() => Some(vec![]),
+            _ => None,
+        }
     }
 }
 
@@ -110,22 +303,46 @@ impl Action for RemovePartitionAction {
         format!("rm({}/)", self.path)
     }
 
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::remove(self.path.clone())]
+    }
+
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
-        let new_state = state.remove_partition(&self.path)?;
+        let new_state = self.apply(state)?;
         store.remove_partition(&self.path)?;
 
         Ok(new_state)
     }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        state.remove_partition(&self.path)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        match state.list_objects(&self.path) {
+            Ok(objects) if objects.is_empty() => Some(vec![]),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RemoveObjectAction {
     path: ObjectPath,
+    copy: Option<ObjectPath>,
 }
 
 impl RemoveObjectAction {
     pub fn new(path: ObjectPath) -> Self {
-        Self { path }
+        Self { path, copy: None }
+    }
+
+    /// Removes an object that was copied to `copy`, from which it can be restored on rollback.
+    pub fn new_copied(path: ObjectPath, copy: ObjectPath) -> Self {
+        Self {
+            path,
+            copy: Some(copy),
+        }
     }
 }
 
@@ -134,12 +351,66 @@ impl Action for RemoveObjectAction {
         format!("remove({})", self.path)
     }
 
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::remove(self.path.clone())]
+    }
+
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
-        let new_state = state.remove_object(&self.path)?;
+        let new_state = self.apply(state)?;
         store.remove_object(&self.path)?;
 
         Ok(new_state)
     }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        state.remove_object(&self.path)
+    }
+
+    fn inverse(&self, _: &State) -> Option<Actions> {
+        self.copy
+            .as_ref()
+            .map(|copy| vec![Box::new(CopyAction::new(copy.clone(), self.path.clone())) as _])
+    }
+}
+
+#[derive(Debug)]
+pub struct CopyAction {
+    source: ObjectPath,
+    target: ObjectPath,
+}
+
+impl CopyAction {
+    pub fn new(source: ObjectPath, target: ObjectPath) -> Self {
+        Self { source, target }
+    }
+}
+
+impl Action for CopyAction {
+    fn key(&self) -> String {
+        format!("copy({}, {})", self.source, self.target)
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        vec![
+            Effect::read(self.source.clone()),
+            Effect::create(self.target.clone()),
+        ]
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        let new_state = self.apply(state)?;
+        store.copy_object(&self.source, &self.target)?;
+
+        Ok(new_state)
+    }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        state.copy_object(&self.source, &self.target)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        remove_created_object(state, &self.target)
+    }
 }
 
 #[derive(Debug)]
@@ -159,12 +430,46 @@ impl Action for MoveAction {
         format!("move({}, {})", self.source, self.target)
     }
 
+    fn effects(&self) -> Vec<Effect> {
+        vec![
+            Effect::remove(self.source.clone()),
+            Effect::create(self.target.clone()),
+        ]
+    }
+
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
-        let new_state = state.move_object(&self.source, &self.target)?;
+        let new_state = self.apply(state)?;
         store.move_object(&self.source, &self.target)?;
 
         Ok(new_state)
     }
+
+    fn apply(&self, state: &State) -> Result<State> {
+        state.move_object(&self.source, &self.target)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        if state.contains_object(&self.target) {
+            return None;
+        }
+
+        let mut inverse: Actions = vec![Box::new(MoveAction::new(
+            self.target.clone(),
+            self.source.clone(),
+        ))];
+        inverse.extend(remove_created_partition(state, &self.target));
+        Some(inverse)
+    }
+
+    fn split_move(&self) -> Option<(Box<dyn Action>, Box<dyn Action>)> {
+        Some((
+            Box::new(CopyAction::new(self.source.clone(), self.target.clone())),
+            Box::new(RemoveObjectAction::new_copied(
+                self.source.clone(),
+                self.target.clone(),
+            )),
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -172,15 +477,29 @@ pub struct RebalanceAction {
     paths: Vec<ObjectPath>,
     size: Bytes,
     count: usize,
+    format: Format,
 }
 
 impl RebalanceAction {
-    pub fn new(paths: Vec<ObjectPath>, size: Bytes, count: usize) -> Self {
-        Self { paths, size, count }
+    /// Fails unless the objects are all of the same known format, the one of the outputs.
+    pub fn new(paths: Vec<ObjectPath>, size: Bytes, count: usize) -> Result<Self> {
+        let format = common_format(&paths)?;
+        Ok(Self {
+            paths,
+            size,
+            count,
+            format,
+        })
     }
 
-    fn format(&self) -> Option<Format> {
-        self.paths[0].infer_format()
+    fn output_paths(&self) -> Vec<ObjectPath> {
+        (0..self.count)
+            .map(|idx| {
+                self.paths[0]
+                    .partition_path()
+                    .object_path(&ObjectKey::new(format!("{}.{}", idx, self.format)))
+            })
+            .collect()
     }
 }
 
@@ -194,33 +513,25 @@ impl Action for RebalanceAction {
         format!("rebalance({}, {})", paths.join(", "), self.count)
     }
 
+    fn effects(&self) -> Vec<Effect> {
+        let reads = self.paths.iter().cloned().map(Effect::read);
+        let creates = self.output_paths().into_iter().map(Effect::create);
+        reads.chain(creates).collect()
+    }
+
     fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
         let total_rows = self
             .paths
             .iter()
             .map(|path| state.get_object(path).map_or(None, |object| object.num_rows()))
-            .fold(Some(0), |acc, num| {
-                match (acc, num) {
-                    (Some(acc), Some(num)) => Some(acc + num),
-                    (_, _) => None,
-                }
-            });
+            .sum::<Option<usize>>();
 
         let target = match total_rows {
             Some(rows) => RebalanceTarget::Rows(rows / self.count),
             None => RebalanceTarget::Size(self.size),
         };
 
-        // FIXME: Validate format
-        let format = self.format().unwrap();
-
-        let output_paths = (0..self.count)
-            .map(|idx| {
-                self.paths[0]
-                    .partition_path()
-                    .object_path(&ObjectKey::new(format!("{}.{}", idx, format)))
-            })
-            .collect::<Vec<ObjectPath>>();
+        let output_paths = self.output_paths();
 
         let object_states = store.rebalance_objects(self.paths.as_slice(), &output_paths, &target)?;
 
@@ -232,6 +543,354 @@ impl Action for RebalanceAction {
 
         Ok(new_state)
     }
+
+    /// Assumes the objects are rebalanced evenly, in the format of the first object.
+    fn apply(&self, state: &State) -> Result<State> {
+        let mut object = state.get_object(&self.paths[0])?.clone();
+        let mut total_size = Bytes::new(0);
+        let mut total_rows = 0;
+
+        for path in &self.paths {
+            let input = state.get_object(path)?;
+            total_size = total_size + input.size;
+            total_rows += input.num_rows().unwrap_or(0);
+        }
+
+        object.size = total_size.mul(1.0 / self.count as f64);
+
+        if let FormatState::Parquet(format) = &object.format {
+            object.format = FormatState::Parquet(ParquetFormatState::new(
+                format.schema().clone(),
+                total_rows / self.count,
+            ));
+        }
+
+        let mut new_state = state.clone();
+
+        for path in self.output_paths() {
+            new_state = new_state.insert_object(&path, object.clone())?;
+        }
+
+        Ok(new_state)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        let output_paths = self.output_paths();
+
+        match output_paths.iter().any(|path| state.contains_object(path)) {
+            true => None,
+            false => Some(
+                output_paths
+                    .into_iter()
+                    .map(|path| Box::new(RemoveObjectAction::new(path)) as Box<dyn Action>)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Writes a random sample of the rows of an object into a new object.
+#[derive(Debug)]
+pub struct SampleAction {
+    source: ObjectPath,
+    target: ObjectPath,
+    sampler: Sampler,
+}
+
+impl SampleAction {
+    pub fn new(source: ObjectPath, target: ObjectPath, sampler: Sampler) -> Self {
+        Self {
+            source,
+            target,
+            sampler,
+        }
+    }
+}
+
+impl Action for SampleAction {
+    fn key(&self) -> String {
+        format!(
+            "sample({}, {}, {})",
+            self.source,
+            self.target,
+            self.sampler.percent()
+        )
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        vec![
+            Effect::read(self.source.clone()),
+            Effect::create(self.target.clone()),
+        ]
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        let object = store.sample_object(&self.source, &self.target, &self.sampler)?;
+        state.insert_object(&self.target, object)
+    }
+
+    /// Assumes the sample keeps exactly its percentage of the size and rows of the source.
+    fn apply(&self, state: &State) -> Result<State> {
+        let mut object = state.get_object(&self.source)?.clone();
+        let fraction = self.sampler.percent().as_fraction();
+
+        object.size = object.size.mul(fraction);
+
+        if let FormatState::Parquet(format) = &object.format {
+            object.format = FormatState::Parquet(ParquetFormatState::new(
+                format.schema().clone(),
+                (format.num_rows() as f64 * fraction) as usize,
+            ));
+        }
+
+        state.insert_object(&self.target, object)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        remove_created_object(state, &self.target)
+    }
+}
+
+/// Rewrites objects into the partitions of a dataset given by the values of their rows.
+#[derive(Debug)]
+pub struct RepartitionAction {
+    paths: Vec<ObjectPath>,
+    target: DatasetPath,
+    partitioner: Partitioner,
+}
+
+impl RepartitionAction {
+    pub fn new(paths: Vec<ObjectPath>, target: DatasetPath, partitioner: Partitioner) -> Self {
+        Self {
+            paths,
+            target,
+            partitioner,
+        }
+    }
+}
+
+impl Action for RepartitionAction {
+    fn key(&self) -> String {
+        let paths = self
+            .paths
+            .iter()
+            .map(|p| format!("{}", p))
+            .collect::<Vec<String>>();
+        format!(
+            "repartition({}, {}, {})",
+            paths.join(", "),
+            self.target,
+            self.partitioner.keys().join("/")
+        )
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        let reads = self.paths.iter().cloned().map(Effect::read);
+        reads
+            .chain(std::iter::once(Effect::create(self.target.clone())))
+            .collect()
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        let objects = store.repartition_objects(&self.paths, &self.target, &self.partitioner)?;
+
+        let mut new_state = self.apply(state)?;
+        for (path, object) in objects {
+            new_state = new_state.insert_object(&path, object)?;
+        }
+
+        Ok(new_state)
+    }
+
+    /// The partitions depend on the values of the rows, only the target dataset is predicted.
+    fn apply(&self, state: &State) -> Result<State> {
+        match state.contains_dataset(&self.target) {
+            true => Ok(state.clone()),
+            false => state.insert_dataset(&self.target, DatasetState::default()),
+        }
+    }
+
+    /// The objects written are only known once the action ran.
+    fn inverse(&self, _: &State) -> Option<Actions> {
+        None
+    }
+}
+
+/// Rewrites an object compressed, possibly in place.
+#[derive(Debug)]
+pub struct CompressAction {
+    source: ObjectPath,
+    target: ObjectPath,
+    compression: Compression,
+}
+
+impl CompressAction {
+    pub fn new(source: ObjectPath, target: ObjectPath, compression: Compression) -> Self {
+        Self {
+            source,
+            target,
+            compression,
+        }
+    }
+}
+
+impl Action for CompressAction {
+    fn key(&self) -> String {
+        format!(
+            "compress({}, {}, {})",
+            self.source, self.target, self.compression
+        )
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        match self.source == self.target {
+            true => vec![Effect::update(self.source.clone())],
+            false => vec![
+                Effect::read(self.source.clone()),
+                Effect::create(self.target.clone()),
+            ],
+        }
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        let object = store.compress_object(&self.source, &self.target, &self.compression)?;
+        state.insert_object(&self.target, object)
+    }
+
+    /// The compressed size is only known once the object is written, the size is kept as is.
+    fn apply(&self, state: &State) -> Result<State> {
+        state.copy_object(&self.source, &self.target)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        remove_created_object(state, &self.target)
+    }
+}
+
+/// Writes an object of random rows.
+#[derive(Debug)]
+pub struct GenerateAction {
+    path: ObjectPath,
+    generator: Generator,
+    rows: usize,
+}
+
+impl GenerateAction {
+    pub fn new(path: ObjectPath, generator: Generator, rows: usize) -> Self {
+        Self {
+            path,
+            generator,
+            rows,
+        }
+    }
+}
+
+impl Action for GenerateAction {
+    fn key(&self) -> String {
+        format!(
+            "generate({}, {}, {})",
+            self.path,
+            self.generator.columns(),
+            self.rows
+        )
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        vec![Effect::create(self.path.clone())]
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        let object = store.generate_object(&self.path, &self.generator, self.rows)?;
+        state.insert_object(&self.path, object)
+    }
+
+    /// The size is only known once the object is written, it is predicted empty.
+    fn apply(&self, state: &State) -> Result<State> {
+        let schema = self.generator.schema();
+
+        let object = match self.path.infer_format() {
+            Some(Format::Csv) => ObjectState::new_csv(
+                CsvFormatState::new((*schema).clone(), ",".to_string()),
+                Bytes::new(0),
+            ),
+            Some(Format::Parquet) => {
+                let descriptor = arrow_to_parquet_schema(&schema)?;
+                ObjectState::new_parquet(
+                    ParquetFormatState::new(descriptor.root_schema().clone(), self.rows),
+                    Bytes::new(0),
+                )
+            }
+            None => return Err(StoreError::CannotInferSchema(self.path.clone()).into()),
+        };
+
+        state.insert_object(&self.path, object)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        remove_created_object(state, &self.path)
+    }
+}
+
+/// Rewrites a CSV object as a Parquet object, or the reverse.
+#[derive(Clone, Debug)]
+pub struct ConvertAction {
+    source: ObjectPath,
+    target: ObjectPath,
+}
+
+impl ConvertAction {
+    pub fn new(source: ObjectPath, target: ObjectPath) -> Self {
+        Self { source, target }
+    }
+}
+
+impl Action for ConvertAction {
+    fn key(&self) -> String {
+        format!("convert({}, {})", self.source, self.target)
+    }
+
+    fn effects(&self) -> Vec<Effect> {
+        vec![
+            Effect::read(self.source.clone()),
+            Effect::create(self.target.clone()),
+        ]
+    }
+
+    fn execute(&self, store: &dyn Store, state: &State) -> Result<State> {
+        let object = store.convert_object(&self.source, &self.target)?;
+        state.insert_object(&self.target, object)
+    }
+
+    /// Assumes the object keeps its size, the rows of a CSV object are only known once it is
+    /// written as Parquet.
+    fn apply(&self, state: &State) -> Result<State> {
+        let source = state.get_object(&self.source)?;
+
+        let object = match (&source.format, self.target.infer_format()) {
+            (FormatState::Csv(format), Some(Format::Parquet)) => {
+                let descriptor = arrow_to_parquet_schema(format.schema())?;
+                ObjectState::new_parquet(
+                    ParquetFormatState::new(descriptor.root_schema().clone(), 0),
+                    source.size,
+                )
+            }
+            (FormatState::Parquet(format), Some(Format::Csv)) => {
+                let descriptor = SchemaDescriptor::new(Arc::new(format.schema().clone()));
+                let schema = parquet_to_arrow_schema(&descriptor, &None)?;
+                ObjectState::new_csv(CsvFormatState::new(schema, ",".to_string()), source.size)
+            }
+            _ => {
+                let error = StoreError::CannotConvert(self.source.clone(), self.target.clone());
+                return Err(error.into());
+            }
+        };
+
+        state.insert_object(&self.target, object)
+    }
+
+    fn inverse(&self, state: &State) -> Option<Actions> {
+        remove_created_object(state, &self.target)
+    }
 }
 
 pub type Key = usize;
@@ -245,6 +904,12 @@ pub struct ActionTree {
     actions: HashMap<Key, Actions>,
 }
 
+impl Default for ActionTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ActionTree {
     pub fn new() -> Self {
         Self {
@@ -262,12 +927,46 @@ impl ActionTree {
         tree
     }
 
+    /// Builds a tree of layers from actions listed in the order they must take effect. Every
+    /// action runs in the layer after the last earlier action it has conflicting effects with.
+    pub fn from_effects(actions: Actions) -> Self {
+        let mut index = EffectIndex::default();
+        let mut layers: Vec<Actions> = Vec::new();
+
+        for action in actions {
+            let effects = action.effects();
+            let layer = index.layer(&effects);
+            index.record(&effects, layer);
+
+            if layers.len() <= layer {
+                layers.resize_with(layer + 1, Vec::new);
+            }
+            layers[layer].push(action);
+        }
+
+        let mut tree = Self::new();
+        let mut previous_key = None;
+
+        for layer in layers {
+            let key = match previous_key {
+                Some(previous_key) => tree.add_node(&[previous_key]),
+                None => tree.add_node(&[]),
+            };
+            for action in layer {
+                tree.add_action(key, action);
+            }
+            previous_key = Some(key);
+        }
+
+        tree
+    }
+
     pub fn add_node(&mut self, dependencies: &[Key]) -> Key {
         let key = self.next_key;
         self.next_key += 1;
 
         for dependency in dependencies {
-            let upstream = self.upstream.entry(key).or_insert_with(Keys::new);
+            let upstream = self.upstream.entry(key).or_default();
             upstream.insert(*dependency);
         }
 
@@ -279,7 +978,7 @@ impl ActionTree {
     }
 
     pub fn add_action(&mut self, key: Key, action: Box<dyn Action>) {
-        let entry = self.actions.entry(key).or_insert_with(Vec::new);
+        let entry = self.actions.entry(key).or_default();
         entry.push(action);
     }
 
@@ -287,6 +986,60 @@ impl ActionTree {
         self.next_key - 1
     }
 
+    /// Keys of every action of the tree.
+    pub fn action_keys(&self) -> Vec<String> {
+        self.actions
+            .values()
+            .flatten()
+            .map(|action| action.key())
+            .collect()
+    }
+
+    /// Rewrites every node containing moves into a node of copies, followed by a new node
+    /// removing the sources. Nodes that depended on the original node wait for the removals.
+    pub fn split_moves(mut self) -> Self {
+        for key in 1..self.next_key {
+            let actions = match self.actions.remove(&key) {
+                Some(actions) => actions,
+                None => continue,
+            };
+
+            let mut copies = Vec::new();
+            let mut removes = Vec::new();
+
+            for action in actions {
+                match action.split_move() {
+                    Some((copy, remove)) => {
+                        copies.push(copy);
+                        removes.push(remove);
+                    }
+                    None => copies.push(action),
+                }
+            }
+
+            self.actions.insert(key, copies);
+
+            if !removes.is_empty() {
+                let remove_key = self.insert_after(key);
+                self.actions.insert(remove_key, removes);
+            }
+        }
+
+        self
+    }
+
+    fn insert_after(&mut self, key: Key) -> Key {
+        let new_key = self.add_node(&[key]);
+
+        for (downstream_key, upstream_keys) in self.upstream.iter_mut() {
+            if *downstream_key != new_key && upstream_keys.remove(&key) {
+                upstream_keys.insert(new_key);
+            }
+        }
+
+        new_key
+    }
+
     pub fn next_batch(&self, completed: &Keys) -> Vec<(Key, Vec<&dyn Action>)> {
         if completed.is_empty() {
             return self
@@ -2,6 +2,48 @@ use std::ffi::OsStr;
 use std::fmt;
 use std::ops::Add;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error("Unknown protocol: {0}")]
+    UnknownProtocol(String),
+
+    #[error("Invalid bucket: {0}")]
+    InvalidBucket(String),
+
+    #[error("Invalid partition: {0}")]
+    InvalidPartition(String),
+
+    #[error("Invalid size: {0}")]
+    InvalidSize(String),
+
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+
+    #[error("Invalid percent: {0}")]
+    InvalidPercent(String),
+
+    #[error("Invalid number: {0}")]
+    InvalidNumber(String),
+
+    #[error("Unknown codec: {0}")]
+    UnknownCodec(String),
+
+    #[error("Invalid column: {0}, expected name:int|float|string|timestamp")]
+    InvalidColumn(String),
+
+    #[error("Unknown format: {0}")]
+    UnknownFormat(String),
+
+    #[error("Unknown store method: {0}")]
+    UnknownStoreMethod(String),
+
+    #[error("Invalid fault: {0}, expected method,pattern,fail|partial|latency:<ms>ms[,percent]")]
+    InvalidFault(String),
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Format {
@@ -18,6 +60,18 @@ impl fmt::Display for Format {
     }
 }
 
+impl FromStr for Format {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "parquet" => Ok(Format::Parquet),
+            _ => Err(ParseError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Protocol {
     File,
@@ -34,6 +88,18 @@ impl fmt::Display for Protocol {
     }
 }
 
+impl FromStr for Protocol {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Protocol::File),
+            "s3" => Ok(Protocol::S3),
+            _ => Err(ParseError::UnknownProtocol(s.to_string())),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct ObjectKey(String);
 
@@ -53,6 +119,16 @@ impl ObjectKey {
     pub fn extension(&self) -> Option<&str> {
         self.0.split('.').collect::<Vec<&str>>().get(1).copied()
     }
+
+    pub fn is_gzipped(&self) -> bool {
+        self.0.ends_with(".gz")
+    }
+
+    /// Same key with the extension of a format, e.g. `001.parquet` for `001.csv`.
+    pub fn with_format(&self, format: &Format) -> Self {
+        let stem = self.0.split('.').next().unwrap_or_default();
+        Self(format!("{}.{}", stem, format))
+    }
 }
 
 impl fmt::Display for ObjectKey {
@@ -61,11 +137,18 @@ impl fmt::Display for ObjectKey {
     }
 }
 
+/// FNV-1a, stable across platforms and compiler versions unlike the std hasher.
+pub fn stable_hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 pub trait ToStdPath {
     fn std_path(&self) -> PathBuf;
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Partition {
     values: Vec<(String, String)>,
 }
@@ -84,6 +167,30 @@ impl Partition {
     }
 }
 
+impl FromStr for Partition {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut partition: Option<Partition> = None;
+
+        for segment in s.trim_matches('/').split('/') {
+            let (key, value) = match segment.find('=') {
+                Some(idx) if idx > 0 && idx < segment.len() - 1 => {
+                    (segment[0..idx].to_string(), segment[idx + 1..].to_string())
+                }
+                _ => return Err(ParseError::InvalidPartition(s.to_string())),
+            };
+
+            partition = Some(match partition {
+                Some(partition) => partition.push(key, value),
+                None => Partition::new(key, value),
+            });
+        }
+
+        partition.ok_or_else(|| ParseError::InvalidPartition(s.to_string()))
+    }
+}
+
 impl ToStdPath for Partition {
     fn std_path(&self) -> PathBuf {
         let mut buf = PathBuf::new();
@@ -96,10 +203,12 @@ impl ToStdPath for Partition {
 
 impl fmt::Display for Partition {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for (key, value) in &self.values {
-            write!(f, "{}={}", key, value)?;
-        }
-        Ok(())
+        let values = self
+            .values
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>();
+        write!(f, "{}", values.join("/"))
     }
 }
 
@@ -121,6 +230,23 @@ impl fmt::Display for Bucket {
     }
 }
 
+impl FromStr for Bucket {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let idx = s
+            .find("://")
+            .ok_or_else(|| ParseError::InvalidBucket(s.to_string()))?;
+        let name = &s[idx + 3..];
+
+        if name.is_empty() || name.contains('/') {
+            return Err(ParseError::InvalidBucket(s.to_string()));
+        }
+
+        Ok(Bucket::new(s[0..idx].parse()?, name.to_string()))
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Output(String);
 
@@ -152,6 +278,10 @@ impl Bytes {
         Self(size * Self::MIB)
     }
 
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+
     pub fn mul(&self, multiplier: f64) -> Self {
         Self((self.0 as f64 * multiplier) as usize)
     }
@@ -175,6 +305,28 @@ impl fmt::Display for Bytes {
     }
 }
 
+impl FromStr for Bytes {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (digits, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+            Some(idx) => s.split_at(idx),
+            None => (s, "B"),
+        };
+
+        let size = digits
+            .parse()
+            .map_err(|_| ParseError::InvalidSize(s.to_string()))?;
+
+        match unit.trim() {
+            "B" => Ok(Self::new(size)),
+            "KiB" => Ok(Self::new_in_kib(size)),
+            "MiB" => Ok(Self::new_in_mib(size)),
+            _ => Err(ParseError::InvalidSize(s.to_string())),
+        }
+    }
+}
+
 impl Add for Bytes {
     type Output = Self;
 
@@ -182,3 +334,38 @@ impl Add for Bytes {
         Self::new(self.0 + other.0)
     }
 }
+
+/// A whole percentage between 0 and 100, written with or without a trailing `%`.
+#[derive(Debug, Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Percent(u8);
+
+impl Percent {
+    pub fn new(percent: u8) -> Option<Self> {
+        match percent {
+            0..=100 => Some(Self(percent)),
+            _ => None,
+        }
+    }
+
+    pub fn as_fraction(&self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+impl FromStr for Percent {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim_end_matches('%')
+            .parse()
+            .ok()
+            .and_then(Self::new)
+            .ok_or_else(|| ParseError::InvalidPercent(s.to_string()))
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
+use parquet::basic::Compression as ParquetCompression;
+
+use crate::base::{ObjectKey, ParseError};
+
+/// Codec of the pages of Parquet objects.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Codec {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Brotli,
+    Lz4,
+    Zstd,
+}
+
+impl Codec {
+    pub fn parquet_compression(&self) -> ParquetCompression {
+        match self {
+            Codec::Uncompressed => ParquetCompression::UNCOMPRESSED,
+            Codec::Snappy => ParquetCompression::SNAPPY,
+            Codec::Gzip => ParquetCompression::GZIP,
+            Codec::Brotli => ParquetCompression::BROTLI,
+            Codec::Lz4 => ParquetCompression::LZ4,
+            Codec::Zstd => ParquetCompression::ZSTD,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match self {
+            Codec::Uncompressed => "uncompressed",
+            Codec::Snappy => "snappy",
+            Codec::Gzip => "gzip",
+            Codec::Brotli => "brotli",
+            Codec::Lz4 => "lz4",
+            Codec::Zstd => "zstd",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+impl FromStr for Codec {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uncompressed" => Ok(Codec::Uncompressed),
+            "snappy" => Ok(Codec::Snappy),
+            "gzip" => Ok(Codec::Gzip),
+            "brotli" => Ok(Codec::Brotli),
+            "lz4" => Ok(Codec::Lz4),
+            "zstd" => Ok(Codec::Zstd),
+            _ => Err(ParseError::UnknownCodec(s.to_string())),
+        }
+    }
+}
+
+/// How objects are rewritten: Parquet objects with a codec, and CSV objects gzipped with a
+/// level from 0 to 9 if any. The Parquet writer only supports the default level of codecs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Compression {
+    pub codec: Codec,
+    pub csv_level: Option<u32>,
+}
+
+impl Compression {
+    pub const MAX_CSV_LEVEL: u32 = 9;
+
+    pub fn new(codec: Codec, csv_level: Option<u32>) -> Self {
+        Self { codec, csv_level }
+    }
+
+    /// Key of the object written for an object, gzipped CSV objects end with `.gz`.
+    pub fn output_key(&self, key: &ObjectKey) -> ObjectKey {
+        match key.is_gzipped() {
+            false if self.csv_level.is_some() && key.extension() == Some("csv") => {
+                ObjectKey::new(format!("{}.gz", key))
+            }
+            _ => key.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.csv_level {
+            Some(level) => write!(f, "{}, csv: gzip-{}", self.codec, level),
+            None => write!(f, "{}", self.codec),
+        }
+    }
+}
@@ -1,14 +1,26 @@
 use anyhow::Result;
 use arrow::csv;
-use std::io;
+use arrow::record_batch::RecordBatch;
+use std::collections::hash_map::Entry;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use parquet::file::writer::ParquetWriter;
+use std::collections::HashMap;
+use std::io::{self, Read};
 
-use crate::base::Bytes;
+use crate::base::{Bytes, Partition};
+use crate::parquet::Parquet;
+use crate::repartition::Partitioner;
 use crate::state::{CsvFormatState, ObjectState};
 
 pub struct Csv {}
 
 impl Csv {
     const BATCH_SIZE: usize = 2048 * 10;
+    const GZIPPED_HEAD_SIZE: u64 = 1024 * 1024;
+
+    /// Number of first bytes of an object enough to infer its schema.
+    pub const HEAD_SIZE: u64 = 64 * 1024;
 
     pub fn read_object_state<R: 'static + io::Read + io::Seek>(mut reader: R) -> Result<ObjectState> {
         let size = reader.seek(io::SeekFrom::End(0))?;
@@ -22,10 +34,103 @@ impl Csv {
         Ok(ObjectState::new_csv(format_state, Bytes::new(size as usize)))
     }
 
+    /// Reads the state of a gzipped object, inferring its schema from its first rows only.
+    pub fn read_gzipped_object_state<R: 'static + io::Read + io::Seek>(
+        mut reader: R,
+    ) -> Result<ObjectState> {
+        let size = reader.seek(io::SeekFrom::End(0))?;
+        reader.seek(io::SeekFrom::Start(0))?;
+
+        let mut head = vec![];
+        GzDecoder::new(reader)
+            .take(Self::GZIPPED_HEAD_SIZE)
+            .read_to_end(&mut head)?;
+
+        let builder = csv::ReaderBuilder::new().infer_schema(Some(10));
+        let csv_reader = builder.build(io::Cursor::new(head))?;
+        let schema = csv_reader.schema();
+        let format_state = CsvFormatState::new((*schema).clone(), ",".to_string());
+
+        Ok(ObjectState::new_csv(format_state, Bytes::new(size as usize)))
+    }
+
+    /// Reads the state of an object from its first bytes only, e.g. fetched with a ranged read.
+    /// The head of a gzipped object is decoded up to where it was cut.
+    pub fn read_head_object_state(
+        head: Vec<u8>,
+        size: Bytes,
+        gzipped: bool,
+    ) -> Result<ObjectState> {
+        let head = match gzipped {
+            true => {
+                let mut decoded = vec![];
+                let result = GzDecoder::new(head.as_slice())
+                    .take(Self::GZIPPED_HEAD_SIZE)
+                    .read_to_end(&mut decoded);
+                match result {
+                    Err(error) if error.kind() != io::ErrorKind::UnexpectedEof => {
+                        return Err(error.into())
+                    }
+                    _ => decoded,
+                }
+            }
+            false => head,
+        };
+
+        let builder = csv::ReaderBuilder::new().infer_schema(Some(10));
+        let csv_reader = builder.build(io::Cursor::new(head))?;
+        let schema = csv_reader.schema();
+        let format_state = CsvFormatState::new((*schema).clone(), ",".to_string());
+
+        Ok(ObjectState::new_csv(format_state, size))
+    }
+
+    /// Writes batches of rows into a new object.
+    pub fn write_object<W: io::Write>(
+        writer: W,
+        batches: impl Iterator<Item = Result<RecordBatch>>,
+    ) -> Result<()> {
+        let mut writer = csv::Writer::new(writer);
+
+        for batch_result in batches {
+            writer.write(&batch_result?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the rows of an object into a new Parquet object, with the schema inferred from
+    /// its first rows.
+    pub fn convert_object<R: 'static + io::Read + io::Seek, W: 'static + ParquetWriter>(
+        reader: R,
+        writer: W,
+    ) -> Result<()> {
+        let csv_reader = csv::ReaderBuilder::new()
+            .infer_schema(Some(Self::BATCH_SIZE))
+            .with_batch_size(Self::BATCH_SIZE)
+            .has_header(true)
+            .build(reader)?;
+        let schema = csv_reader.schema();
+
+        Parquet::write_object(writer, schema, csv_reader.map(|batch| Ok(batch?)))
+    }
+
+    /// Writes an object gzipped with a level from 0 to 9.
+    pub fn gzip_object<R: io::Read, W: io::Write>(
+        mut reader: R,
+        writer: W,
+        level: u32,
+    ) -> Result<()> {
+        let mut encoder = GzEncoder::new(writer, flate2::Compression::new(level));
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
     pub fn combine_objects<R: 'static + io::Read + io::Seek, W: 'static + io::Write>(
         readers: Vec<R>,
         mut writers: Vec<W>,
-        is_writer_full: Box<dyn Fn(usize) -> bool>,
+        is_writer_full: Box<dyn Fn(usize) -> Result<bool>>,
     ) -> Result<()> {
         let mut writer_idx = 0;
         let mut writer = csv::Writer::new(writers.remove(0));
@@ -38,7 +143,7 @@ impl Csv {
                 .build(reader)?;
 
             for batch_result in csv_reader {
-                if is_writer_full(writer_idx) && !writers.is_empty() {
+                if !writers.is_empty() && is_writer_full(writer_idx)? {
                     writer = csv::Writer::new(writers.remove(0));
                     writer_idx += 1;
                 }
@@ -50,4 +155,57 @@ impl Csv {
 
         Ok(())
     }
+
+    /// Writes the rows of an object kept by `filter` into a new object.
+    pub fn filter_object<R: 'static + io::Read + io::Seek, W: 'static + io::Write>(
+        reader: R,
+        writer: W,
+        mut filter: impl FnMut(&RecordBatch) -> Result<RecordBatch>,
+    ) -> Result<()> {
+        let csv_reader = csv::ReaderBuilder::new()
+            .infer_schema(Some(Self::BATCH_SIZE))
+            .with_batch_size(Self::BATCH_SIZE)
+            .has_header(true)
+            .build(reader)?;
+        let mut writer = csv::Writer::new(writer);
+
+        for batch_result in csv_reader {
+            writer.write(&filter(&batch_result?)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the rows of objects into one new object per partition, created on the first row
+    /// of the partition.
+    pub fn partition_objects<R: 'static + io::Read + io::Seek, W: 'static + io::Write>(
+        readers: Vec<R>,
+        partitioner: &Partitioner,
+        mut create_writer: impl FnMut(&Partition) -> Result<W>,
+    ) -> Result<()> {
+        let mut writers: HashMap<Partition, csv::Writer<W>> = HashMap::new();
+
+        for reader in readers {
+            let csv_reader = csv::ReaderBuilder::new()
+                .infer_schema(Some(Self::BATCH_SIZE))
+                .with_batch_size(Self::BATCH_SIZE)
+                .has_header(true)
+                .build(reader)?;
+
+            for batch_result in csv_reader {
+                for (partition, batch) in partitioner.split(&batch_result?)? {
+                    let writer = match writers.entry(partition) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let writer = create_writer(entry.key())?;
+                            entry.insert(csv::Writer::new(writer))
+                        }
+                    };
+                    writer.write(&batch)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
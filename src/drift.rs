@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::base::Bytes;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::state::{FormatState, ObjectState, State};
+
+#[derive(Error, Debug)]
+pub enum DriftError {
+    #[error("Dataset {0} does not match the store:\n{1}")]
+    Drifted(DatasetPath, Drift),
+}
+
+#[derive(Clone, Debug)]
+pub enum Difference {
+    Size(Bytes, Bytes),
+    Format(String, String),
+    Schema,
+}
+
+impl Difference {
+    /// Differences between the state of an object and the object found in the store.
+    fn between(expected: &ObjectState, actual: &ObjectState) -> Vec<Difference> {
+        let mut differences = vec![];
+
+        if expected.size != actual.size {
+            differences.push(Difference::Size(expected.size, actual.size));
+        }
+
+        let (expected_format, actual_format) =
+            (expected.format.to_string(), actual.format.to_string());
+        if expected_format != actual_format {
+            differences.push(Difference::Format(expected_format, actual_format));
+        }
+
+        // Schemas of different formats are already reported as a format difference
+        let same_schema = match (&expected.format, &actual.format) {
+            (FormatState::Csv(expected), FormatState::Csv(actual)) => {
+                expected.schema() == actual.schema()
+            }
+            (FormatState::Parquet(expected), FormatState::Parquet(actual)) => {
+                expected.schema() == actual.schema()
+            }
+            _ => true,
+        };
+        if !same_schema {
+            differences.push(Difference::Schema);
+        }
+
+        differences
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Difference::Size(expected, actual) => json!({
+                "kind": "size",
+                "expected": expected.as_usize(),
+                "actual": actual.as_usize(),
+            }),
+            Difference::Format(expected, actual) => {
+                json!({"kind": "format", "expected": expected, "actual": actual})
+            }
+            Difference::Schema => json!({"kind": "schema"}),
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Size(expected, actual) => write!(f, "size: {} -> {}", expected, actual),
+            Difference::Format(expected, actual) => {
+                write!(f, "format: {} -> {}", expected, actual)
+            }
+            Difference::Schema => write!(f, "schema"),
+        }
+    }
+}
+
+/// Differences between a dataset in the state and the same dataset in the store. Objects of
+/// missing or extra partitions are not listed individually.
+#[derive(Clone, Debug, Default)]
+pub struct Drift {
+    pub missing_partitions: Vec<PartitionPath>,
+    pub extra_partitions: Vec<PartitionPath>,
+    pub missing_objects: Vec<ObjectPath>,
+    pub extra_objects: Vec<ObjectPath>,
+    pub changed_objects: Vec<(ObjectPath, Vec<Difference>)>,
+}
+
+impl Drift {
+    /// Compares `path` in the `expected` state with the same dataset freshly loaded in `actual`.
+    pub fn between(path: &DatasetPath, expected: &State, actual: &State) -> Result<Self> {
+        let mut drift = Drift::default();
+
+        let expected_partitions = expected.list_partitions(path)?;
+        let actual_partitions = actual.list_partitions(path)?;
+        let actual_set = actual_partitions.iter().collect::<HashSet<_>>();
+
+        for partition in &expected_partitions {
+            if !actual_set.contains(partition) {
+                drift.missing_partitions.push(partition.clone());
+                continue;
+            }
+
+            let expected_objects = expected.list_objects(partition)?;
+            let actual_objects = actual.list_objects(partition)?;
+
+            for object in &expected_objects {
+                if !actual.contains_object(object) {
+                    drift.missing_objects.push(object.clone());
+                    continue;
+                }
+
+                let differences =
+                    Difference::between(expected.get_object(object)?, actual.get_object(object)?);
+                if !differences.is_empty() {
+                    drift.changed_objects.push((object.clone(), differences));
+                }
+            }
+
+            for object in actual_objects {
+                if !expected.contains_object(&object) {
+                    drift.extra_objects.push(object);
+                }
+            }
+        }
+
+        let expected_set = expected_partitions.iter().collect::<HashSet<_>>();
+        for partition in &actual_partitions {
+            if !expected_set.contains(partition) {
+                drift.extra_partitions.push(partition.clone());
+            }
+        }
+
+        drift.sort();
+        Ok(drift)
+    }
+
+    fn sort(&mut self) {
+        self.missing_partitions.sort_by_key(|path| path.to_string());
+        self.extra_partitions.sort_by_key(|path| path.to_string());
+        self.missing_objects.sort_by_key(|path| path.to_string());
+        self.extra_objects.sort_by_key(|path| path.to_string());
+        self.changed_objects
+            .sort_by_key(|(path, _)| path.to_string());
+    }
+
+    /// Every difference, so that callers can act on the drift rather than parse its message.
+    pub fn to_json(&self) -> Value {
+        let paths = |paths: &[ObjectPath]| {
+            paths
+                .iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<String>>()
+        };
+        let partitions = |paths: &[PartitionPath]| {
+            paths
+                .iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<String>>()
+        };
+        let changed_objects = self
+            .changed_objects
+            .iter()
+            .map(|(path, differences)| {
+                let differences = differences
+                    .iter()
+                    .map(Difference::to_json)
+                    .collect::<Vec<Value>>();
+                json!({"object": path.to_string(), "differences": differences})
+            })
+            .collect::<Vec<Value>>();
+
+        json!({
+            "missing_partitions": partitions(&self.missing_partitions),
+            "extra_partitions": partitions(&self.extra_partitions),
+            "missing_objects": paths(&self.missing_objects),
+            "extra_objects": paths(&self.extra_objects),
+            "changed_objects": changed_objects,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.missing_partitions.is_empty()
+            && self.extra_partitions.is_empty()
+            && self.missing_objects.is_empty()
+            && self.extra_objects.is_empty()
+            && self.changed_objects.is_empty()
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for path in &self.missing_partitions {
+            writeln!(f, "  - missing partition: {}", path)?;
+        }
+        for path in &self.extra_partitions {
+            writeln!(f, "  + extra partition: {}", path)?;
+        }
+        for path in &self.missing_objects {
+            writeln!(f, "  - missing: {}", path)?;
+        }
+        for path in &self.extra_objects {
+            writeln!(f, "  + extra: {}", path)?;
+        }
+        for (path, differences) in &self.changed_objects {
+            let differences = differences
+                .iter()
+                .map(|difference| difference.to_string())
+                .collect::<Vec<String>>();
+            writeln!(f, "  ~ changed: {} ({})", path, differences.join(", "))?;
+        }
+        Ok(())
+    }
+}
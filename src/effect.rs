@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::action::Action;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+
+#[derive(Error, Debug)]
+pub enum EffectError {
+    #[error("Action {0} conflicts with a concurrent action on: {1}")]
+    Conflict(String, Resource),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EffectKind {
+    Read,
+    Update,
+    Create,
+    Remove,
+}
+
+impl EffectKind {
+    pub fn is_write(&self) -> bool {
+        !matches!(self, EffectKind::Read)
+    }
+}
+
+impl fmt::Display for EffectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match self {
+            EffectKind::Read => "read",
+            EffectKind::Update => "update",
+            EffectKind::Create => "create",
+            EffectKind::Remove => "remove",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Resource {
+    Dataset(DatasetPath),
+    Partition(PartitionPath),
+    Object(ObjectPath),
+}
+
+impl Resource {
+    /// The resource followed by every resource containing it.
+    fn lineage(&self) -> Vec<Resource> {
+        match self {
+            Resource::Dataset(_) => vec![self.clone()],
+            Resource::Partition(path) => {
+                vec![self.clone(), Resource::Dataset(path.dataset.clone())]
+            }
+            Resource::Object(path) => vec![
+                self.clone(),
+                Resource::Partition(path.partition_path().clone()),
+                Resource::Dataset(path.dataset_path().clone()),
+            ],
+        }
+    }
+}
+
+impl From<DatasetPath> for Resource {
+    fn from(path: DatasetPath) -> Self {
+        Resource::Dataset(path)
+    }
+}
+
+impl From<PartitionPath> for Resource {
+    fn from(path: PartitionPath) -> Self {
+        Resource::Partition(path)
+    }
+}
+
+impl From<ObjectPath> for Resource {
+    fn from(path: ObjectPath) -> Self {
+        Resource::Object(path)
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resource::Dataset(path) => write!(f, "{}", path),
+            Resource::Partition(path) => write!(f, "{}", path),
+            Resource::Object(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Effect {
+    pub resource: Resource,
+    pub kind: EffectKind,
+}
+
+impl Effect {
+    pub fn new<R: Into<Resource>>(kind: EffectKind, resource: R) -> Self {
+        Self {
+            resource: resource.into(),
+            kind,
+        }
+    }
+
+    pub fn read<R: Into<Resource>>(resource: R) -> Self {
+        Self::new(EffectKind::Read, resource)
+    }
+
+    pub fn update<R: Into<Resource>>(resource: R) -> Self {
+        Self::new(EffectKind::Update, resource)
+    }
+
+    pub fn create<R: Into<Resource>>(resource: R) -> Self {
+        Self::new(EffectKind::Create, resource)
+    }
+
+    pub fn remove<R: Into<Resource>>(resource: R) -> Self {
+        Self::new(EffectKind::Remove, resource)
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.resource, self.kind)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Marks {
+    read: Option<usize>,
+    write: Option<usize>,
+    subtree_read: Option<usize>,
+    subtree_write: Option<usize>,
+}
+
+/// Latest layers in which every resource, or anything it contains, was read or written.
+#[derive(Debug, Default)]
+pub struct EffectIndex {
+    marks: HashMap<Resource, Marks>,
+}
+
+impl EffectIndex {
+    /// Latest layer of a recorded effect that conflicts with `effect`. Effects conflict when
+    /// one of their resources contains the other and at least one of them writes.
+    pub fn conflict(&self, effect: &Effect) -> Option<usize> {
+        let mut latest = None;
+
+        for (idx, resource) in effect.resource.lineage().iter().enumerate() {
+            let marks = match self.marks.get(resource) {
+                Some(marks) => marks,
+                None => continue,
+            };
+
+            let (read, write) = match idx {
+                0 => (marks.subtree_read, marks.subtree_write),
+                _ => (marks.read, marks.write),
+            };
+
+            latest = latest.max(write);
+            if effect.kind.is_write() {
+                latest = latest.max(read);
+            }
+        }
+
+        latest
+    }
+
+    /// First layer in which an action with `effects` runs after everything it conflicts with.
+    pub fn layer(&self, effects: &[Effect]) -> usize {
+        effects
+            .iter()
+            .filter_map(|effect| self.conflict(effect))
+            .map(|layer| layer + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn record(&mut self, effects: &[Effect], layer: usize) {
+        for effect in effects {
+            let is_write = effect.kind.is_write();
+
+            for (idx, resource) in effect.resource.lineage().into_iter().enumerate() {
+                let marks = self.marks.entry(resource).or_default();
+
+                match (idx, is_write) {
+                    (0, true) => marks.write = marks.write.max(Some(layer)),
+                    (0, false) => marks.read = marks.read.max(Some(layer)),
+                    _ => {}
+                }
+
+                match is_write {
+                    true => marks.subtree_write = marks.subtree_write.max(Some(layer)),
+                    false => marks.subtree_read = marks.subtree_read.max(Some(layer)),
+                }
+            }
+        }
+    }
+}
+
+/// Fails if any of the actions, which are about to run concurrently, have conflicting effects.
+pub fn check_conflicts(actions: &[&dyn Action]) -> Result<()> {
+    let mut index = EffectIndex::default();
+
+    for action in actions {
+        let effects = action.effects();
+
+        if let Some(effect) = effects.iter().find(|e| index.conflict(e).is_some()) {
+            return Err(EffectError::Conflict(action.key(), effect.resource.clone()).into());
+        }
+
+        index.record(&effects, 0);
+    }
+
+    Ok(())
+}
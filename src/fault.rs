@@ -0,0 +1,501 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use thiserror::Error;
+
+use crate::base::{stable_hash, ObjectKey, ParseError, Partition, Percent};
+use crate::compress::Compression;
+use crate::generate::Generator;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
+use crate::state::ObjectState;
+use crate::store::{RebalanceTarget, Store};
+
+#[derive(Error, Debug)]
+pub enum FaultError {
+    #[error("Injected failure of {0} on {1}")]
+    Failure(StoreMethod, String),
+
+    #[error("Injected partial write of {0} on {1}")]
+    PartialWrite(StoreMethod, String),
+}
+
+impl FaultError {
+    /// Injected faults stand for transient failures, calls may succeed if made again.
+    pub fn is_retryable(&self) -> bool {
+        true
+    }
+}
+
+/// Methods of the `Store` trait, written as their name, e.g. `move_object`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StoreMethod {
+    ReadObject,
+    OpenObject,
+    WriteObject,
+    CopyObject,
+    MoveObject,
+    ListPartitions,
+    ListObjects,
+    RemoveDataset,
+    RemovePartition,
+    RemoveObject,
+    RebalanceObjects,
+    SampleObject,
+    RepartitionObjects,
+    CompressObject,
+    GenerateObject,
+    ConvertObject,
+}
+
+impl StoreMethod {
+    const ALL: [StoreMethod; 16] = [
+        StoreMethod::ReadObject,
+        StoreMethod::OpenObject,
+        StoreMethod::WriteObject,
+        StoreMethod::CopyObject,
+        StoreMethod::MoveObject,
+        StoreMethod::ListPartitions,
+        StoreMethod::ListObjects,
+        StoreMethod::RemoveDataset,
+        StoreMethod::RemovePartition,
+        StoreMethod::RemoveObject,
+        StoreMethod::RebalanceObjects,
+        StoreMethod::SampleObject,
+        StoreMethod::RepartitionObjects,
+        StoreMethod::CompressObject,
+        StoreMethod::GenerateObject,
+        StoreMethod::ConvertObject,
+    ];
+}
+
+impl fmt::Display for StoreMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match self {
+            StoreMethod::ReadObject => "read_object",
+            StoreMethod::OpenObject => "open_object",
+            StoreMethod::WriteObject => "write_object",
+            StoreMethod::CopyObject => "copy_object",
+            StoreMethod::MoveObject => "move_object",
+            StoreMethod::ListPartitions => "list_partitions",
+            StoreMethod::ListObjects => "list_objects",
+            StoreMethod::RemoveDataset => "remove_dataset",
+            StoreMethod::RemovePartition => "remove_partition",
+            StoreMethod::RemoveObject => "remove_object",
+            StoreMethod::RebalanceObjects => "rebalance_objects",
+            StoreMethod::SampleObject => "sample_object",
+            StoreMethod::RepartitionObjects => "repartition_objects",
+            StoreMethod::CompressObject => "compress_object",
+            StoreMethod::GenerateObject => "generate_object",
+            StoreMethod::ConvertObject => "convert_object",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+impl FromStr for StoreMethod {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|method| method.to_string() == s)
+            .copied()
+            .ok_or_else(|| ParseError::UnknownStoreMethod(s.to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FaultKind {
+    /// The call fails without reaching the store.
+    Fail,
+    /// The call writes only the first half of its objects, then fails. Calls writing no
+    /// object fail without reaching the store.
+    PartialWrite,
+    /// The call reaches the store after a delay.
+    Latency(Duration),
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaultKind::Fail => write!(f, "fail"),
+            FaultKind::PartialWrite => write!(f, "partial"),
+            FaultKind::Latency(delay) => write!(f, "latency:{}ms", delay.as_millis()),
+        }
+    }
+}
+
+/// Fault of the calls of a method, or of any method, on paths matching a pattern where `*`
+/// matches any characters, e.g. `move_object,file://example/*/day=1/*,fail,50%`. Only the given
+/// percentage of the matching calls is faulty, all of them by default.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    method: Option<StoreMethod>,
+    pattern: String,
+    kind: FaultKind,
+    percent: Percent,
+}
+
+impl Fault {
+    pub fn new(
+        method: Option<StoreMethod>,
+        pattern: String,
+        kind: FaultKind,
+        percent: Percent,
+    ) -> Self {
+        Self {
+            method,
+            pattern,
+            kind,
+            percent,
+        }
+    }
+
+    fn matches(&self, method: StoreMethod, paths: &[String]) -> bool {
+        self.method.is_none_or(|m| m == method)
+            && paths
+                .iter()
+                .any(|path| Self::matches_pattern(self.pattern.as_bytes(), path.as_bytes()))
+    }
+
+    fn matches_pattern(pattern: &[u8], s: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => s.is_empty(),
+            Some((b'*', rest)) => (0..=s.len()).any(|idx| Self::matches_pattern(rest, &s[idx..])),
+            Some((c, rest)) => s.first() == Some(c) && Self::matches_pattern(rest, &s[1..]),
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let method = match self.method {
+            Some(method) => method.to_string(),
+            None => "*".to_string(),
+        };
+        write!(
+            f,
+            "{},{},{},{}",
+            method, self.pattern, self.kind, self.percent
+        )
+    }
+}
+
+impl FromStr for Fault {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseError::InvalidFault(s.to_string());
+        let fields = s.split(',').map(str::trim).collect::<Vec<&str>>();
+
+        let (method, pattern, kind, percent) = match fields.as_slice() {
+            [method, pattern, kind] => (method, pattern, kind, "100%"),
+            [method, pattern, kind, percent] => (method, pattern, kind, *percent),
+            _ => return Err(invalid()),
+        };
+
+        let method = match *method {
+            "*" => None,
+            method => Some(method.parse()?),
+        };
+        let kind = match *kind {
+            "fail" => FaultKind::Fail,
+            "partial" => FaultKind::PartialWrite,
+            kind => {
+                let millis = kind
+                    .strip_prefix("latency:")
+                    .and_then(|delay| delay.strip_suffix("ms"))
+                    .and_then(|millis| millis.parse().ok())
+                    .ok_or_else(invalid)?;
+                FaultKind::Latency(Duration::from_millis(millis))
+            }
+        };
+
+        Ok(Fault::new(
+            method,
+            pattern.to_string(),
+            kind,
+            percent.parse()?,
+        ))
+    }
+}
+
+/// Store injecting faults into the calls of another store. Whether a call is faulty depends on
+/// the seed, the method, its paths and how many times it was called with them before, so that
+/// the same calls get the same faults whatever the order workers make them in.
+pub struct FaultyStore {
+    inner: Box<dyn Store>,
+    faults: Vec<Fault>,
+    seed: u64,
+    calls: Mutex<HashMap<String, u64>>,
+}
+
+impl FaultyStore {
+    pub fn new(inner: Box<dyn Store>, faults: Vec<Fault>, seed: u64) -> Self {
+        Self {
+            inner,
+            faults,
+            seed,
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Applies the latency of the faults of a call, and fails if one of them is a failure.
+    /// Returns whether the call must only partially write its objects.
+    fn inject(&self, method: StoreMethod, paths: &[String]) -> Result<bool> {
+        let matching = self
+            .faults
+            .iter()
+            .filter(|fault| fault.matches(method, paths))
+            .collect::<Vec<&Fault>>();
+        if matching.is_empty() {
+            return Ok(false);
+        }
+
+        let call = format!("{} {}", method, paths.join(" "));
+        let count = {
+            let mut calls = self.calls.lock().expect("fault calls lock poisoned");
+            let count = calls.entry(call.clone()).or_insert(0);
+            *count += 1;
+            *count
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed ^ stable_hash(&call) ^ count);
+
+        let mut partial = false;
+        for fault in matching {
+            if !rng.gen_bool(fault.percent.as_fraction()) {
+                continue;
+            }
+
+            match fault.kind {
+                FaultKind::Fail => return Err(FaultError::Failure(method, paths.join(", ")).into()),
+                FaultKind::PartialWrite => partial = true,
+                FaultKind::Latency(delay) => thread::sleep(delay),
+            }
+        }
+
+        Ok(partial)
+    }
+
+    /// Injects the faults of a call writing no object, partial writes being failures.
+    fn inject_read(&self, method: StoreMethod, paths: &[String]) -> Result<()> {
+        match self.inject(method, paths)? {
+            true => Err(FaultError::Failure(method, paths.join(", ")).into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Truncates objects written by a call to their first half, and fails.
+    fn write_partially<T>(&self, method: StoreMethod, paths: &[ObjectPath]) -> Result<T> {
+        for path in paths {
+            let mut data = vec![];
+            self.inner.open_object(path)?.read_to_end(&mut data)?;
+            data.truncate(data.len() / 2);
+            self.inner.write_object(path, &mut data.as_slice())?;
+        }
+
+        let paths = paths
+            .iter()
+            .map(|path| path.to_string())
+            .collect::<Vec<String>>();
+        Err(FaultError::PartialWrite(method, paths.join(", ")).into())
+    }
+}
+
+impl Store for FaultyStore {
+    fn read_object(&self, path: &ObjectPath) -> Result<ObjectState> {
+        self.inject_read(StoreMethod::ReadObject, &[path.to_string()])?;
+        self.inner.read_object(path)
+    }
+
+    fn open_object(&self, path: &ObjectPath) -> Result<Box<dyn io::Read + Send>> {
+        self.inject_read(StoreMethod::OpenObject, &[path.to_string()])?;
+        self.inner.open_object(path)
+    }
+
+    fn write_object(&self, path: &ObjectPath, reader: &mut dyn io::Read) -> Result<()> {
+        let method = StoreMethod::WriteObject;
+        if !self.inject(method, &[path.to_string()])? {
+            return self.inner.write_object(path, reader);
+        }
+
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        self.inner
+            .write_object(path, &mut &data[0..data.len() / 2])?;
+        Err(FaultError::PartialWrite(method, path.to_string()).into())
+    }
+
+    fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let method = StoreMethod::CopyObject;
+        let partial = self.inject(method, &[source.to_string(), target.to_string()])?;
+
+        self.inner.copy_object(source, target)?;
+        match partial {
+            true => self.write_partially(method, std::slice::from_ref(target)),
+            false => Ok(()),
+        }
+    }
+
+    /// A partial move copies the object partially and leaves the source behind.
+    fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let method = StoreMethod::MoveObject;
+        match self.inject(method, &[source.to_string(), target.to_string()])? {
+            true => {
+                self.inner.copy_object(source, target)?;
+                self.write_partially(method, std::slice::from_ref(target))
+            }
+            false => self.inner.move_object(source, target),
+        }
+    }
+
+    fn supports_move(&self) -> bool {
+        self.inner.supports_move()
+    }
+
+    fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<Partition>> {
+        self.inject_read(StoreMethod::ListPartitions, &[path.to_string()])?;
+        self.inner.list_partitions(path)
+    }
+
+    fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectKey>> {
+        self.inject_read(StoreMethod::ListObjects, &[path.to_string()])?;
+        self.inner.list_objects(path)
+    }
+
+    fn remove_dataset(&self, path: &DatasetPath) -> Result<()> {
+        self.inject_read(StoreMethod::RemoveDataset, &[path.to_string()])?;
+        self.inner.remove_dataset(path)
+    }
+
+    fn remove_partition(&self, path: &PartitionPath) -> Result<()> {
+        self.inject_read(StoreMethod::RemovePartition, &[path.to_string()])?;
+        self.inner.remove_partition(path)
+    }
+
+    fn remove_object(&self, path: &ObjectPath) -> Result<()> {
+        self.inject_read(StoreMethod::RemoveObject, &[path.to_string()])?;
+        self.inner.remove_object(path)
+    }
+
+    fn rebalance_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        output_paths: &[ObjectPath],
+        target: &RebalanceTarget,
+    ) -> Result<Vec<ObjectState>> {
+        let method = StoreMethod::RebalanceObjects;
+        let paths = input_paths
+            .iter()
+            .chain(output_paths)
+            .map(|path| path.to_string())
+            .collect::<Vec<String>>();
+        let partial = self.inject(method, &paths)?;
+
+        let states = self
+            .inner
+            .rebalance_objects(input_paths, output_paths, target)?;
+        match partial {
+            true => self.write_partially(method, output_paths),
+            false => Ok(states),
+        }
+    }
+
+    fn sample_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        sampler: &Sampler,
+    ) -> Result<ObjectState> {
+        let method = StoreMethod::SampleObject;
+        let partial = self.inject(method, &[source.to_string(), target.to_string()])?;
+
+        let state = self.inner.sample_object(source, target, sampler)?;
+        match partial {
+            true => self.write_partially(method, std::slice::from_ref(target)),
+            false => Ok(state),
+        }
+    }
+
+    fn repartition_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        target: &DatasetPath,
+        partitioner: &Partitioner,
+    ) -> Result<Vec<(ObjectPath, ObjectState)>> {
+        let method = StoreMethod::RepartitionObjects;
+        let paths = input_paths
+            .iter()
+            .map(|path| path.to_string())
+            .chain(std::iter::once(target.to_string()))
+            .collect::<Vec<String>>();
+        let partial = self.inject(method, &paths)?;
+
+        let outputs = self
+            .inner
+            .repartition_objects(input_paths, target, partitioner)?;
+        match partial {
+            true => {
+                let output_paths = outputs
+                    .into_iter()
+                    .map(|(path, _)| path)
+                    .collect::<Vec<ObjectPath>>();
+                self.write_partially(method, &output_paths)
+            }
+            false => Ok(outputs),
+        }
+    }
+
+    fn compress_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        compression: &Compression,
+    ) -> Result<ObjectState> {
+        let method = StoreMethod::CompressObject;
+        let partial = self.inject(method, &[source.to_string(), target.to_string()])?;
+
+        let state = self.inner.compress_object(source, target, compression)?;
+        match partial {
+            true => self.write_partially(method, std::slice::from_ref(target)),
+            false => Ok(state),
+        }
+    }
+
+    fn generate_object(
+        &self,
+        path: &ObjectPath,
+        generator: &Generator,
+        rows: usize,
+    ) -> Result<ObjectState> {
+        let method = StoreMethod::GenerateObject;
+        let partial = self.inject(method, &[path.to_string()])?;
+
+        let state = self.inner.generate_object(path, generator, rows)?;
+        match partial {
+            true => self.write_partially(method, std::slice::from_ref(path)),
+            false => Ok(state),
+        }
+    }
+
+    fn convert_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<ObjectState> {
+        let method = StoreMethod::ConvertObject;
+        let partial = self.inject(method, &[source.to_string(), target.to_string()])?;
+
+        let state = self.inner.convert_object(source, target)?;
+        match partial {
+            true => self.write_partially(method, std::slice::from_ref(target)),
+            false => Ok(state),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray, TimestampSecondArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use rand::distributions::Alphanumeric;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::base::{ParseError, Percent};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColumnKind {
+    Int,
+    Float,
+    String,
+    Timestamp,
+}
+
+impl ColumnKind {
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnKind::Int => DataType::Int64,
+            ColumnKind::Float => DataType::Float64,
+            ColumnKind::String => DataType::Utf8,
+            ColumnKind::Timestamp => DataType::Timestamp(TimeUnit::Second, None),
+        }
+    }
+}
+
+impl fmt::Display for ColumnKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match self {
+            ColumnKind::Int => "int",
+            ColumnKind::Float => "float",
+            ColumnKind::String => "string",
+            ColumnKind::Timestamp => "timestamp",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+/// Random rows of a schema: ints from 0 to 1M, floats from 0 to 1K, alphanumeric strings of
+/// 8 characters and timestamps during 2020, each of them null with a given ratio. The same
+/// seed generates the same rows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generator {
+    columns: Columns,
+    null_ratio: Percent,
+    seed: u64,
+}
+
+impl Generator {
+    const BATCH_SIZE: usize = 2048 * 10;
+
+    const MAX_INT: i64 = 1_000_000;
+    const MAX_FLOAT: f64 = 1_000.0;
+    const STRING_LENGTH: usize = 8;
+    /// 2020-01-01T00:00:00Z, and the number of seconds in the leap year that follows.
+    const MIN_TIMESTAMP: i64 = 1_577_836_800;
+    const TIMESTAMP_RANGE: i64 = 366 * 24 * 60 * 60;
+
+    pub fn new(columns: Columns, null_ratio: Percent, seed: u64) -> Self {
+        Self {
+            columns,
+            null_ratio,
+            seed,
+        }
+    }
+
+    pub fn columns(&self) -> &Columns {
+        &self.columns
+    }
+
+    pub fn null_ratio(&self) -> Percent {
+        self.null_ratio
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Every column is nullable, unless the null ratio is 0%.
+    pub fn schema(&self) -> SchemaRef {
+        let nullable = self.null_ratio.as_fraction() > 0.0;
+        let fields = self
+            .columns
+            .0
+            .iter()
+            .map(|(name, kind)| Field::new(name, kind.data_type(), nullable))
+            .collect();
+        Arc::new(Schema::new(fields))
+    }
+
+    /// Batches of `rows` rows in total.
+    pub fn batches(&self, rows: usize) -> impl Iterator<Item = Result<RecordBatch>> {
+        let generator = self.clone();
+        let schema = self.schema();
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut remaining = rows;
+
+        std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+
+            let size = remaining.min(Self::BATCH_SIZE);
+            remaining -= size;
+
+            let columns = generator
+                .columns
+                .0
+                .iter()
+                .map(|(_, kind)| generator.column(&mut rng, *kind, size))
+                .collect();
+            Some(RecordBatch::try_new(schema.clone(), columns).map_err(Into::into))
+        })
+    }
+
+    fn column(&self, rng: &mut ChaCha8Rng, kind: ColumnKind, size: usize) -> ArrayRef {
+        match kind {
+            ColumnKind::Int => {
+                let values = (0..size)
+                    .map(|_| self.value(rng, |rng| rng.gen_range(0, Self::MAX_INT)))
+                    .collect::<Vec<Option<i64>>>();
+                Arc::new(Int64Array::from(values))
+            }
+            ColumnKind::Float => {
+                let values = (0..size)
+                    .map(|_| self.value(rng, |rng| rng.gen::<f64>() * Self::MAX_FLOAT))
+                    .collect::<Vec<Option<f64>>>();
+                Arc::new(Float64Array::from(values))
+            }
+            ColumnKind::String => {
+                let values = (0..size)
+                    .map(|_| {
+                        self.value(rng, |rng| {
+                            (0..Self::STRING_LENGTH)
+                                .map(|_| rng.sample(Alphanumeric))
+                                .collect::<String>()
+                        })
+                    })
+                    .collect::<Vec<Option<String>>>();
+                Arc::new(StringArray::from(
+                    values
+                        .iter()
+                        .map(|value| value.as_deref())
+                        .collect::<Vec<Option<&str>>>(),
+                ))
+            }
+            ColumnKind::Timestamp => {
+                let values = (0..size)
+                    .map(|_| {
+                        self.value(rng, |rng| {
+                            Self::MIN_TIMESTAMP + rng.gen_range(0, Self::TIMESTAMP_RANGE)
+                        })
+                    })
+                    .collect::<Vec<Option<i64>>>();
+                Arc::new(TimestampSecondArray::from_opt_vec(values, None))
+            }
+        }
+    }
+
+    /// A random value, or null with the null ratio of the generator.
+    fn value<T>(
+        &self,
+        rng: &mut ChaCha8Rng,
+        generate: impl FnOnce(&mut ChaCha8Rng) -> T,
+    ) -> Option<T> {
+        match rng.gen_bool(self.null_ratio.as_fraction()) {
+            true => None,
+            false => Some(generate(rng)),
+        }
+    }
+}
+
+/// Columns of generated rows, written as `name:kind;...`, e.g. `id:int;at:timestamp`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Columns(Vec<(String, ColumnKind)>);
+
+impl fmt::Display for Columns {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let columns = self
+            .0
+            .iter()
+            .map(|(name, kind)| format!("{}:{}", name, kind))
+            .collect::<Vec<String>>();
+        write!(f, "{}", columns.join(";"))
+    }
+}
+
+impl FromStr for Columns {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let columns = s
+            .split(';')
+            .map(|column| {
+                let invalid = || ParseError::InvalidColumn(column.to_string());
+
+                let (name, kind) = match column.find(':') {
+                    Some(idx) if idx > 0 => (column[0..idx].trim(), column[idx + 1..].trim()),
+                    _ => return Err(invalid()),
+                };
+                let kind = match kind {
+                    "int" => ColumnKind::Int,
+                    "float" => ColumnKind::Float,
+                    "string" => ColumnKind::String,
+                    "timestamp" => ColumnKind::Timestamp,
+                    _ => return Err(invalid()),
+                };
+
+                Ok((name.to_string(), kind))
+            })
+            .collect::<Result<Vec<(String, ColumnKind)>, ParseError>>()?;
+
+        Ok(Columns(columns))
+    }
+}
@@ -1,34 +1,123 @@
 use anyhow::Result;
+use thiserror::Error;
 
 use crate::action::{
-    ActionTree, MoveAction, RebalanceAction, ReloadDatasetAction, RemoveObjectAction,
-    RemovePartitionAction,
+    ActionTree, Actions, CompressAction, ConvertAction, CopyAction, GenerateAction, MoveAction,
+    RebalanceAction, ReloadDatasetAction, RemoveDatasetAction, RemoveObjectAction,
+    RemovePartitionAction, RepartitionAction, SampleAction, VerifyDatasetAction,
 };
-use crate::base::Bytes;
-use crate::path::{DatasetPath, PartitionPath};
+use crate::base::{Bytes, Format, Partition, Percent};
+use crate::compress::Compression;
+use crate::generate::Generator;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
 use crate::state::State;
 
+#[derive(Error, Debug)]
+pub enum JobError {
+    #[error("Source and target are the same: {0}")]
+    SameSourceAndTarget(String),
+
+    #[error("Repartitioning requires at least one partition key")]
+    MissingPartitionKeys,
+
+    #[error("Invalid gzip level: {0}, expected 0 to 9")]
+    InvalidCompressionLevel(u32),
+
+    #[error("Cannot convert compressed object: {0}")]
+    CompressedObject(String),
+}
+
 pub trait Job {
     fn actions(&self, state: &State) -> Result<ActionTree>;
+
+    /// Datasets that must be loaded in the state before building the actions.
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![]
+    }
+
+    /// Datasets written to by the job, loaded beforehand only if they already exist.
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![]
+    }
+}
+
+/// Actions removing every object and partition of a dataset, e.g. the target of a job that
+/// rewrites it. None if the dataset is not in the state.
+fn clear_dataset(state: &State, path: &DatasetPath) -> Result<Actions> {
+    let mut actions: Actions = vec![];
+
+    if state.contains_dataset(path) {
+        for partition in state.list_partitions(path)? {
+            for object in state.list_objects(&partition)? {
+                actions.push(Box::new(RemoveObjectAction::new(object)))
+            }
+            actions.push(Box::new(RemovePartitionAction::new(partition)))
+        }
+    }
+
+    Ok(actions)
 }
 
 pub struct ReloadDataset {
     path: DatasetPath,
+    allow_missing: bool,
 }
 
 impl ReloadDataset {
     pub fn new(path: DatasetPath) -> Self {
-        ReloadDataset { path }
+        ReloadDataset {
+            path,
+            allow_missing: false,
+        }
+    }
+
+    pub fn new_optional(path: DatasetPath) -> Self {
+        ReloadDataset {
+            path,
+            allow_missing: true,
+        }
+    }
+
+    pub fn path(&self) -> &DatasetPath {
+        &self.path
     }
 }
 
 impl Job for ReloadDataset {
     fn actions(&self, _: &State) -> Result<ActionTree> {
-        let reload = ReloadDatasetAction::new(self.path.clone());
+        let reload = match self.allow_missing {
+            true => ReloadDatasetAction::new_optional(self.path.clone()),
+            false => ReloadDatasetAction::new(self.path.clone()),
+        };
         Ok(ActionTree::single(Box::new(reload)))
     }
 }
 
+/// Compares a dataset of the state with the store, failing with the differences if any.
+pub struct VerifyDataset {
+    path: DatasetPath,
+}
+
+impl VerifyDataset {
+    pub fn new(path: DatasetPath) -> Self {
+        VerifyDataset { path }
+    }
+}
+
+impl Job for VerifyDataset {
+    fn actions(&self, _: &State) -> Result<ActionTree> {
+        Ok(ActionTree::single(Box::new(VerifyDatasetAction::new(
+            self.path.clone(),
+        ))))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.path.clone()]
+    }
+}
+
 pub struct MovePartition {
     source: PartitionPath,
     target: PartitionPath,
@@ -42,34 +131,182 @@ impl MovePartition {
 
 impl Job for MovePartition {
     fn actions(&self, state: &State) -> Result<ActionTree> {
-        let mut actions = ActionTree::new();
-
-        let remove_target_node = actions.add_node(&[]);
+        let mut actions: Actions = vec![];
 
         if state.contains_partition(&self.target) {
             for object in state.list_objects(&self.target)? {
-                actions.add_action(
-                    remove_target_node,
-                    Box::new(RemoveObjectAction::new(object)),
-                )
+                actions.push(Box::new(RemoveObjectAction::new(object)))
             }
         }
 
-        let copy_node = actions.add_node(&[remove_target_node]);
-
         for object in state.list_objects(&self.source)? {
-            // FIXME: Object stores support copy and not move
             let target = object.update_partition(&self.target.partition);
-            actions.add_action(copy_node, Box::new(MoveAction::new(object, target)))
+            actions.push(Box::new(MoveAction::new(object, target)))
         }
 
-        let remove_partition_node = actions.add_node(&[copy_node]);
-        actions.add_action(
-            remove_partition_node,
-            Box::new(RemovePartitionAction::new(self.source.clone())),
-        );
+        actions.push(Box::new(RemovePartitionAction::new(self.source.clone())));
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.dataset.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.dataset.clone()]
+    }
+}
+
+pub struct CopyPartition {
+    source: PartitionPath,
+    target: PartitionPath,
+}
 
-        Ok(actions)
+impl CopyPartition {
+    pub fn new(source: PartitionPath, target: PartitionPath) -> Self {
+        CopyPartition { source, target }
+    }
+}
+
+impl Job for CopyPartition {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        if self.source == self.target {
+            return Err(JobError::SameSourceAndTarget(self.source.to_string()).into());
+        }
+
+        let mut actions: Actions = vec![];
+
+        if state.contains_partition(&self.target) {
+            for object in state.list_objects(&self.target)? {
+                actions.push(Box::new(RemoveObjectAction::new(object)))
+            }
+        }
+
+        for object in state.list_objects(&self.source)? {
+            let target = self.target.object_path(&object.key);
+            actions.push(Box::new(CopyAction::new(object, target)))
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.dataset.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.dataset.clone()]
+    }
+}
+
+pub struct CopyDataset {
+    source: DatasetPath,
+    target: DatasetPath,
+}
+
+impl CopyDataset {
+    pub fn new(source: DatasetPath, target: DatasetPath) -> Self {
+        CopyDataset { source, target }
+    }
+}
+
+impl Job for CopyDataset {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        if self.source == self.target {
+            return Err(JobError::SameSourceAndTarget(self.source.to_string()).into());
+        }
+
+        let mut actions = clear_dataset(state, &self.target)?;
+
+        for partition in state.list_partitions(&self.source)? {
+            for object in state.list_objects(&partition)? {
+                let target = self.target.object_path(object.get_partition(), &object.key);
+                actions.push(Box::new(CopyAction::new(object, target)))
+            }
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.clone()]
+    }
+}
+
+pub struct MoveDataset {
+    source: DatasetPath,
+    target: DatasetPath,
+}
+
+impl MoveDataset {
+    pub fn new(source: DatasetPath, target: DatasetPath) -> Self {
+        MoveDataset { source, target }
+    }
+}
+
+impl Job for MoveDataset {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        if self.source == self.target {
+            return Err(JobError::SameSourceAndTarget(self.source.to_string()).into());
+        }
+
+        let mut actions = clear_dataset(state, &self.target)?;
+
+        for partition in state.list_partitions(&self.source)? {
+            for object in state.list_objects(&partition)? {
+                let target = self.target.object_path(object.get_partition(), &object.key);
+                actions.push(Box::new(MoveAction::new(object, target)))
+            }
+            actions.push(Box::new(RemovePartitionAction::new(partition)))
+        }
+
+        actions.push(Box::new(RemoveDatasetAction::new(self.source.clone())));
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.clone()]
+    }
+}
+
+pub struct RemoveDataset {
+    path: DatasetPath,
+}
+
+impl RemoveDataset {
+    pub fn new(path: DatasetPath) -> Self {
+        RemoveDataset { path }
+    }
+}
+
+impl Job for RemoveDataset {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        let mut actions: Actions = vec![];
+
+        for partition in state.list_partitions(&self.path)? {
+            for object in state.list_objects(&partition)? {
+                actions.push(Box::new(RemoveObjectAction::new(object)))
+            }
+            actions.push(Box::new(RemovePartitionAction::new(partition)))
+        }
+
+        actions.push(Box::new(RemoveDatasetAction::new(self.path.clone())));
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.path.clone()]
     }
 }
 
@@ -86,34 +323,353 @@ impl RebalanceObjects {
 
 impl Job for RebalanceObjects {
     fn actions(&self, state: &State) -> Result<ActionTree> {
-        let mut actions = ActionTree::new();
         let partition_size = state.get_partition(&self.path)?.size();
 
         if partition_size < self.target_size.mul(1.5) {
-            return Ok(actions);
+            return Ok(ActionTree::new());
         }
 
         let objects = state.list_objects(&self.path)?;
         let count = partition_size.div(self.target_size);
 
-        let rebalance_node = actions.add_node(&[]);
-        actions.add_action(
-            rebalance_node,
-            Box::new(RebalanceAction::new(
-                objects.clone(),
-                self.target_size,
-                count,
-            )),
-        );
-
-        let delete_node = actions.add_node(&[rebalance_node]);
-        for object in &objects {
-            actions.add_action(
-                delete_node,
-                Box::new(RemoveObjectAction::new(object.clone())),
-            )
-        }
-
-        Ok(actions)
+        let mut actions: Actions = vec![Box::new(RebalanceAction::new(
+            objects.clone(),
+            self.target_size,
+            count,
+        )?)];
+
+        for object in objects {
+            actions.push(Box::new(RemoveObjectAction::new(object)))
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.path.dataset.clone()]
+    }
+}
+
+/// Replaces a dataset with a random sample of the rows of some partitions of another dataset.
+pub struct Sample {
+    source: DatasetPath,
+    target: DatasetPath,
+    partitions: Vec<Partition>,
+    sampler: Sampler,
+}
+
+impl Sample {
+    /// Samples every partition of the source if `partitions` is empty.
+    pub fn new(
+        source: DatasetPath,
+        target: DatasetPath,
+        partitions: Vec<Partition>,
+        percent: Percent,
+        seed: u64,
+    ) -> Self {
+        Sample {
+            source,
+            target,
+            partitions,
+            sampler: Sampler::new(percent, seed),
+        }
+    }
+}
+
+impl Job for Sample {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        if self.source == self.target {
+            return Err(JobError::SameSourceAndTarget(self.source.to_string()).into());
+        }
+
+        let mut actions = clear_dataset(state, &self.target)?;
+
+        let partitions = match self.partitions.is_empty() {
+            true => state.list_partitions(&self.source)?,
+            false => self
+                .partitions
+                .iter()
+                .map(|partition| self.source.partition_path(partition))
+                .collect(),
+        };
+
+        for partition in partitions {
+            for object in state.list_objects(&partition)? {
+                let target = self.target.object_path(object.get_partition(), &object.key);
+                actions.push(Box::new(SampleAction::new(
+                    object,
+                    target,
+                    self.sampler.clone(),
+                )))
+            }
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.clone()]
+    }
+}
+
+/// Replaces a dataset with the rows of another dataset, partitioned by the values of columns.
+pub struct Repartition {
+    source: DatasetPath,
+    target: DatasetPath,
+    keys: Vec<String>,
+}
+
+impl Repartition {
+    pub fn new(source: DatasetPath, target: DatasetPath, keys: Vec<String>) -> Self {
+        Repartition {
+            source,
+            target,
+            keys,
+        }
+    }
+}
+
+impl Job for Repartition {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        if self.source == self.target {
+            return Err(JobError::SameSourceAndTarget(self.source.to_string()).into());
+        }
+        if self.keys.is_empty() {
+            return Err(JobError::MissingPartitionKeys.into());
+        }
+
+        let mut actions = clear_dataset(state, &self.target)?;
+
+        let mut objects = vec![];
+        for partition in state.list_partitions(&self.source)? {
+            objects.extend(state.list_objects(&partition)?);
+        }
+
+        if !objects.is_empty() {
+            actions.push(Box::new(RepartitionAction::new(
+                objects,
+                self.target.clone(),
+                Partitioner::new(self.keys.clone()),
+            )));
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.clone()]
+    }
+}
+
+/// Rewrites the objects of some partitions of a dataset compressed, into another dataset or
+/// in place. Objects that cannot be compressed are copied as is into another dataset.
+pub struct Compress {
+    source: DatasetPath,
+    target: DatasetPath,
+    partitions: Vec<Partition>,
+    compression: Compression,
+}
+
+impl Compress {
+    /// Compresses every partition of the source if `partitions` is empty.
+    pub fn new(
+        source: DatasetPath,
+        target: DatasetPath,
+        partitions: Vec<Partition>,
+        compression: Compression,
+    ) -> Self {
+        Compress {
+            source,
+            target,
+            partitions,
+            compression,
+        }
+    }
+
+    /// Every object to compress along with the object it is written to.
+    pub fn outputs(&self, state: &State) -> Result<Vec<(ObjectPath, ObjectPath)>> {
+        let partitions = match self.partitions.is_empty() {
+            true => state.list_partitions(&self.source)?,
+            false => self
+                .partitions
+                .iter()
+                .map(|partition| self.source.partition_path(partition))
+                .collect(),
+        };
+
+        let mut outputs = vec![];
+        for partition in partitions {
+            let target_partition = self.target.partition_path(&partition.partition);
+
+            for object in state.list_objects(&partition)? {
+                let key = self.compression.output_key(&object.key);
+                outputs.push((object, target_partition.object_path(&key)));
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    fn is_compressible(&self, object: &ObjectPath) -> bool {
+        match object.infer_format() {
+            Some(Format::Parquet) => true,
+            Some(Format::Csv) => self.compression.csv_level.is_some() && !object.key.is_gzipped(),
+            None => false,
+        }
+    }
+}
+
+impl Job for Compress {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        match self.compression.csv_level {
+            Some(level) if level > Compression::MAX_CSV_LEVEL => {
+                return Err(JobError::InvalidCompressionLevel(level).into())
+            }
+            _ => {}
+        }
+
+        let in_place = self.source == self.target;
+        let outputs = self.outputs(state)?;
+
+        let mut actions = match in_place {
+            true => vec![],
+            false => clear_dataset(state, &self.target)?,
+        };
+
+        for (object, target) in outputs {
+            match (self.is_compressible(&object), in_place) {
+                (true, _) => {
+                    actions.push(Box::new(CompressAction::new(
+                        object.clone(),
+                        target.clone(),
+                        self.compression,
+                    )));
+                    if in_place && target != object {
+                        actions.push(Box::new(RemoveObjectAction::new(object)))
+                    }
+                }
+                (false, false) => actions.push(Box::new(CopyAction::new(object, target))),
+                (false, true) => {}
+            }
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.clone()]
+    }
+}
+
+/// Writes an object of random rows, replacing it if it exists.
+pub struct Generate {
+    path: ObjectPath,
+    generator: Generator,
+    rows: usize,
+}
+
+impl Generate {
+    pub fn new(path: ObjectPath, generator: Generator, rows: usize) -> Self {
+        Generate {
+            path,
+            generator,
+            rows,
+        }
+    }
+}
+
+impl Job for Generate {
+    fn actions(&self, _: &State) -> Result<ActionTree> {
+        Ok(ActionTree::single(Box::new(GenerateAction::new(
+            self.path.clone(),
+            self.generator.clone(),
+            self.rows,
+        ))))
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.path.dataset_path().clone()]
+    }
+}
+
+/// Replaces a dataset with the objects of another dataset written in a format, keeping their
+/// partitions. Objects already in the format are copied.
+pub struct ConvertFormat {
+    source: DatasetPath,
+    target: DatasetPath,
+    format: Format,
+}
+
+impl ConvertFormat {
+    pub fn new(source: DatasetPath, target: DatasetPath, format: Format) -> Self {
+        ConvertFormat {
+            source,
+            target,
+            format,
+        }
+    }
+}
+
+impl Job for ConvertFormat {
+    fn actions(&self, state: &State) -> Result<ActionTree> {
+        if self.source == self.target {
+            return Err(JobError::SameSourceAndTarget(self.source.to_string()).into());
+        }
+
+        let mut objects = vec![];
+        for partition in state.list_partitions(&self.source)? {
+            objects.extend(state.list_objects(&partition)?);
+        }
+
+        // Checked before anything is scheduled, the target is cleared first
+        let converted = |object: &ObjectPath| {
+            object
+                .infer_format()
+                .is_some_and(|format| format != self.format)
+        };
+        if let Some(object) = objects
+            .iter()
+            .find(|object| converted(object) && object.key.is_gzipped())
+        {
+            return Err(JobError::CompressedObject(object.to_string()).into());
+        }
+
+        let mut actions = clear_dataset(state, &self.target)?;
+
+        for object in objects {
+            match converted(&object) {
+                true => {
+                    let key = object.key.with_format(&self.format);
+                    let target = self.target.object_path(object.get_partition(), &key);
+                    actions.push(Box::new(ConvertAction::new(object, target)))
+                }
+                false => {
+                    let target = self.target.object_path(object.get_partition(), &object.key);
+                    actions.push(Box::new(CopyAction::new(object, target)))
+                }
+            }
+        }
+
+        Ok(ActionTree::from_effects(actions))
+    }
+
+    fn dependencies(&self) -> Vec<DatasetPath> {
+        vec![self.source.clone()]
+    }
+
+    fn targets(&self) -> Vec<DatasetPath> {
+        vec![self.target.clone()]
     }
 }
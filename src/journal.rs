@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::snapshot::Snapshot;
+use crate::state::State;
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("Unfinished journal at {0}, resume it or remove it first")]
+    Unfinished(PathBuf),
+
+    #[error("No journal at {0}")]
+    Missing(PathBuf),
+
+    #[error("Invalid journal {0}: {1}")]
+    Invalid(PathBuf, String),
+
+    #[error("Actions of statement {0} do not match the journal: {1}")]
+    Mismatch(usize, String),
+
+    #[error("Journal {0} is opened read-only")]
+    ReadOnly(PathBuf),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Entry {
+    Script { source: String },
+    Begin { statement: usize },
+    Planned { keys: Vec<String> },
+    Started { key: String },
+    Completed { key: String },
+    End { statement: usize },
+    Abort { statement: usize },
+}
+
+/// Durable record of a running script: the statements that ended, and for the statement in
+/// progress, the state it started from along with its planned and completed action keys.
+/// Every entry is synced to disk before the execution moves on.
+pub struct Journal {
+    path: PathBuf,
+    file: Mutex<File>,
+    read_only: bool,
+    source: String,
+    ended: HashSet<usize>,
+    current: Option<usize>,
+    planned: Option<HashSet<String>>,
+    started: HashSet<String>,
+    completed: HashSet<String>,
+}
+
+impl Journal {
+    /// Starts a journal for a script, failing if an unfinished one is already at `path`.
+    pub fn create(path: PathBuf, source: &str) -> Result<Self> {
+        let file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(JournalError::Unfinished(path).into())
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let journal = Self::new(path, file, source.to_string());
+        journal.append(&Entry::Script {
+            source: journal.source.clone(),
+        })?;
+        Ok(journal)
+    }
+
+    /// Reopens an unfinished journal to resume its script.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        Self::load(path, false)
+    }
+
+    /// Reopens an unfinished journal without ever writing to it, e.g. to plan its resumption.
+    pub fn open_read_only(path: PathBuf) -> Result<Self> {
+        Self::load(path, true)
+    }
+
+    fn load(path: PathBuf, read_only: bool) -> Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(JournalError::Missing(path).into())
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut entries = vec![];
+        for line in contents.lines() {
+            match serde_json::from_str::<Entry>(line) {
+                Ok(entry) => entries.push(entry),
+                // The last line is incomplete when the process died while appending it
+                Err(_) if !contents.ends_with('\n') => break,
+                Err(error) => return Err(JournalError::Invalid(path, error.to_string()).into()),
+            }
+        }
+
+        let source = match entries.first() {
+            Some(Entry::Script { source }) => source.clone(),
+            _ => return Err(JournalError::Invalid(path, "missing script".to_string()).into()),
+        };
+
+        let file = OpenOptions::new()
+            .read(read_only)
+            .append(!read_only)
+            .open(&path)?;
+        let mut journal = Self::new(path, file, source);
+        journal.read_only = read_only;
+
+        for entry in entries {
+            match entry {
+                Entry::Script { .. } => {}
+                Entry::Begin { statement } => {
+                    journal.current = Some(statement);
+                    journal.planned = None;
+                    journal.started.clear();
+                    journal.completed.clear();
+                }
+                Entry::Planned { keys } => journal.planned = Some(keys.into_iter().collect()),
+                Entry::Started { key } => {
+                    journal.started.insert(key);
+                }
+                Entry::Completed { key } => {
+                    journal.completed.insert(key);
+                }
+                Entry::End { statement } => {
+                    journal.ended.insert(statement);
+                    journal.current = None;
+                }
+                Entry::Abort { .. } => journal.current = None,
+            }
+        }
+
+        Ok(journal)
+    }
+
+    fn new(path: PathBuf, file: File, source: String) -> Self {
+        Self {
+            path,
+            file: Mutex::new(file),
+            read_only: false,
+            source,
+            ended: HashSet::new(),
+            current: None,
+            planned: None,
+            started: HashSet::new(),
+            completed: HashSet::new(),
+        }
+    }
+
+    fn state_path(&self) -> PathBuf {
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(".state");
+        PathBuf::from(path)
+    }
+
+    fn state_snapshot(&self) -> Snapshot {
+        Snapshot::new(self.state_path())
+    }
+
+    fn append(&self, entry: &Entry) -> Result<()> {
+        if self.read_only {
+            return Err(JournalError::ReadOnly(self.path.clone()).into());
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn has_ended(&self, statement: usize) -> bool {
+        self.ended.contains(&statement)
+    }
+
+    /// The state a statement interrupted while running started from.
+    pub fn resumed_state(&self, statement: usize) -> Result<Option<State>> {
+        match self.current {
+            Some(current) if current == statement => self.state_snapshot().load(),
+            _ => Ok(None),
+        }
+    }
+
+    /// Records the start of a statement and the state it starts from.
+    pub fn begin(&mut self, statement: usize, state: &State) -> Result<()> {
+        if self.current == Some(statement) {
+            return Ok(());
+        }
+
+        self.state_snapshot().save(state)?;
+        self.append(&Entry::Begin { statement })?;
+        self.current = Some(statement);
+        self.planned = None;
+        self.started.clear();
+        self.completed.clear();
+        Ok(())
+    }
+
+    /// Records the keys of the actions of the current statement, or checks them against the
+    /// keys recorded before it was interrupted.
+    pub fn plan(&mut self, keys: Vec<String>) -> Result<()> {
+        let keys = keys.into_iter().collect::<HashSet<String>>();
+
+        match &self.planned {
+            Some(planned) if *planned != keys => {
+                let statement = self.current.unwrap_or_default();
+                let differences = planned
+                    .symmetric_difference(&keys)
+                    .cloned()
+                    .collect::<Vec<String>>();
+                Err(JournalError::Mismatch(statement, differences.join(", ")).into())
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.append(&Entry::Planned {
+                    keys: keys.iter().cloned().collect(),
+                })?;
+                self.planned = Some(keys);
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the action completed before the current statement was interrupted.
+    pub fn is_completed(&self, key: &str) -> bool {
+        self.completed.contains(key)
+    }
+
+    /// Whether the action started but did not complete before the current statement was
+    /// interrupted, in which case the store may or may not reflect it.
+    pub fn is_interrupted(&self, key: &str) -> bool {
+        self.started.contains(key) && !self.completed.contains(key)
+    }
+
+    pub fn start(&self, key: &str) -> Result<()> {
+        self.append(&Entry::Started {
+            key: key.to_string(),
+        })
+    }
+
+    pub fn complete(&self, key: &str) -> Result<()> {
+        self.append(&Entry::Completed {
+            key: key.to_string(),
+        })
+    }
+
+    pub fn end(&mut self, statement: usize) -> Result<()> {
+        self.append(&Entry::End { statement })?;
+        self.ended.insert(statement);
+        self.current = None;
+        Ok(())
+    }
+
+    /// Records that the actions of the current statement were undone, so that it starts over.
+    pub fn abort(&mut self, statement: usize) -> Result<()> {
+        self.append(&Entry::Abort { statement })?;
+        self.current = None;
+        Ok(())
+    }
+
+    /// Removes the journal once its whole script has run, read-only journals are left as is.
+    pub fn finish(self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        match fs::remove_file(self.state_path()) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+pub mod action;
+pub mod base;
+pub mod compress;
+pub mod csv;
+pub mod drift;
+pub mod effect;
+pub mod fault;
+pub mod generate;
+pub mod job;
+pub mod journal;
+pub mod memory;
+pub mod parquet;
+pub mod path;
+pub mod repartition;
+pub mod router;
+pub mod retry;
+pub mod runtime;
+pub mod s3;
+pub mod sample;
+pub mod script;
+pub mod snapshot;
+pub mod state;
+pub mod store;
+pub mod view;
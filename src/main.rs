@@ -1,77 +1,643 @@
-mod action;
-mod base;
-mod csv;
-mod job;
-mod parquet;
-mod path;
-mod runtime;
-mod state;
-mod store;
-mod view;
-
+use std::fs;
 use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use structopt::StructOpt;
+
+use osm::base::{Bytes, Format, Partition, Percent, Protocol};
+use osm::compress::{Codec, Compression};
+use osm::fault::{Fault, FaultyStore};
+use osm::generate::{Columns, Generator};
+use osm::job::{Job, ReloadDataset};
+use osm::journal::Journal;
+use osm::path::{DatasetPath, ObjectPath, PartitionPath};
+use osm::retry::RetryPolicy;
+use osm::router::StoreRouter;
+use osm::runtime::{Execution, Runtime};
+use osm::s3::{S3Config, S3Store};
+use osm::script::{Operation, Script};
+use osm::snapshot::Snapshot;
+use osm::state::State;
+use osm::store::{FileStore, Store};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "osm", about = "Object store maintenance")]
+struct Opt {
+    /// Local directory holding the buckets of the file store
+    #[structopt(
+        long,
+        env = "OSM_ROOT",
+        default_value = "/tmp/osm-root",
+        parse(from_os_str)
+    )]
+    root: PathBuf,
+
+    /// Endpoint of an S3-compatible object store holding the s3:// buckets, e.g.
+    /// http://localhost:9000, with credentials from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+    #[structopt(long, env = "OSM_S3_ENDPOINT")]
+    s3_endpoint: Option<String>,
+
+    /// Region of the S3-compatible object store
+    #[structopt(long, env = "AWS_REGION", default_value = "us-east-1")]
+    s3_region: String,
+
+    /// Number of actions executed concurrently, defaults to the number of CPUs
+    #[structopt(long, env = "OSM_WORKERS")]
+    workers: Option<usize>,
+
+    /// Number of times an action failing with a transient store error is tried again
+    #[structopt(long, env = "OSM_RETRIES", default_value = "0")]
+    retries: usize,
+
+    /// Milliseconds to wait before the first retry of an action, doubled for every next retry
+    #[structopt(long, default_value = "100")]
+    retry_backoff: u64,
+
+    /// File in which the state is kept between runs, to avoid reloading datasets
+    #[structopt(long, env = "OSM_SNAPSHOT", parse(from_os_str))]
+    snapshot: Option<PathBuf>,
+
+    /// File recording the progress of operations, to resume them if they are interrupted
+    #[structopt(long, env = "OSM_JOURNAL", parse(from_os_str))]
+    journal: Option<PathBuf>,
+
+    /// Print the planned actions and the predicted result without changing the store
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Undo the completed actions of an operation when one of its actions fails
+    #[structopt(long, conflicts_with = "dry-run")]
+    rollback: bool,
+
+    /// Print one JSON object per operation instead of human readable output
+    #[structopt(long)]
+    json: bool,
+
+    /// Fault injected into the calls to the store, to test how operations recover, written as
+    /// method or *, path pattern, fail|partial|latency:<ms>ms and the percentage of the matching
+    /// calls, e.g. move_object,file://example/*,fail,50%
+    #[structopt(long = "fault", number_of_values = 1)]
+    faults: Vec<Fault>,
+
+    /// Seed of the injected faults, the same seed fails the same calls
+    #[structopt(long, default_value = "0")]
+    fault_seed: u64,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Reload a dataset from the store
+    Reload { path: DatasetPath },
+
+    /// Check that a dataset of the state, e.g. from a snapshot, still matches the store
+    Verify { path: DatasetPath },
+
+    /// Move every object of a partition into another partition
+    MovePartition {
+        source: PartitionPath,
+        target: PartitionPath,
+    },
+
+    /// Copy every object of a partition into another partition
+    CopyPartition {
+        source: PartitionPath,
+        target: PartitionPath,
+    },
+
+    /// Copy every partition of a dataset into another dataset, possibly in another bucket
+    CopyDataset {
+        source: DatasetPath,
+        target: DatasetPath,
+    },
+
+    /// Move every partition of a dataset into another dataset, possibly in another bucket
+    MoveDataset {
+        source: DatasetPath,
+        target: DatasetPath,
+    },
+
+    /// Remove a dataset with all of its partitions and objects
+    RemoveDataset { path: DatasetPath },
+
+    /// Combine the objects of a partition into objects of a target size
+    Rebalance {
+        path: PartitionPath,
+
+        /// Target object size (e.g. 512KiB, 15MiB)
+        #[structopt(long, default_value = Operation::DEFAULT_REBALANCE_SIZE)]
+        size: Bytes,
+    },
+
+    /// Replace a dataset with a random sample of the rows of another dataset
+    Sample {
+        source: DatasetPath,
+        target: DatasetPath,
+
+        /// Percentage of the rows to keep (e.g. 10, 10%)
+        #[structopt(long)]
+        percent: Percent,
+
+        /// Seed of the random sample, the same seed keeps the same rows
+        #[structopt(long, default_value = "0")]
+        seed: u64,
+
+        /// Partition of the source to sample, can be repeated, defaults to every partition
+        #[structopt(long = "partition")]
+        partitions: Vec<Partition>,
+    },
+
+    /// Replace a dataset with the rows of another dataset, partitioned by the values of columns
+    Repartition {
+        source: DatasetPath,
+        target: DatasetPath,
+
+        /// Columns of the partitions, one partition level per column in the given order
+        #[structopt(required = true)]
+        columns: Vec<String>,
+    },
+
+    /// Rewrite the objects of a dataset compressed, into another dataset or in place
+    Compress {
+        source: DatasetPath,
+        target: DatasetPath,
+
+        /// Codec of Parquet objects at its default level: uncompressed, snappy, gzip, brotli, lz4
+        /// or zstd
+        #[structopt(long, default_value = "zstd")]
+        codec: Codec,
+
+        /// Also gzip CSV objects with this level, from 0 to 9
+        #[structopt(long)]
+        gzip_csv: Option<u32>,
+
+        /// Partition of the source to compress, can be repeated, defaults to every partition
+        #[structopt(long = "partition")]
+        partitions: Vec<Partition>,
+    },
+
+    /// Write an object of random rows, e.g. to build test datasets
+    Generate {
+        path: ObjectPath,
+
+        /// Columns of the rows (e.g. "id:int;fare:float;name:string;pickup:timestamp")
+        #[structopt(long)]
+        columns: Columns,
+
+        /// Number of rows
+        #[structopt(long)]
+        rows: usize,
+
+        /// Percentage of null values
+        #[structopt(long, default_value = "0")]
+        null_ratio: Percent,
+
+        /// Seed of the random rows, the same seed generates the same rows
+        #[structopt(long, default_value = "0")]
+        seed: u64,
+    },
+
+    /// Rewrite the objects of a dataset as CSV or Parquet into another dataset
+    Convert {
+        source: DatasetPath,
+        target: DatasetPath,
+
+        /// Format of the objects written, csv or parquet
+        #[structopt(long)]
+        format: Format,
+    },
+
+    /// List the partitions of a dataset
+    Ls {
+        path: DatasetPath,
+
+        /// Include the objects of every partition
+        #[structopt(short, long)]
+        objects: bool,
+    },
+
+    /// List the objects of a partition
+    LsObjects { path: PartitionPath },
+
+    /// Sum the sizes and rows of the objects of a dataset
+    SizeOf {
+        path: DatasetPath,
+
+        /// Partition to include, can be repeated, defaults to every partition
+        #[structopt(long = "partition")]
+        partitions: Vec<Partition>,
+
+        /// Include the size of every partition and object, and a histogram of object sizes
+        #[structopt(short, long)]
+        detailed: bool,
+    },
+
+    /// Execute a script of operations, e.g. "reload(d1) move-partition(p1, p2)"
+    Exec {
+        /// File containing the script
+        #[structopt(parse(from_os_str), required_unless = "ops")]
+        file: Option<PathBuf>,
+
+        /// Script passed inline instead of through a file
+        #[structopt(long, conflicts_with = "file")]
+        ops: Option<String>,
+
+        /// Continue with the next statement when one fails
+        #[structopt(short, long)]
+        keep_going: bool,
+    },
 
-use anyhow::Result;
+    /// Resume the operations recorded in the journal where they were interrupted
+    Resume {
+        /// Continue with the next statement when one fails
+        #[structopt(short, long)]
+        keep_going: bool,
+    },
 
-use base::{Bucket, Bytes, Partition, Protocol};
-use job::{Job, MovePartition, RebalanceObjects, ReloadDataset};
-use path::DatasetPath;
-use runtime::Runtime;
-use state::State;
-use store::FileStore;
-use view::{ListPartitions, View};
+    /// Mark the snapshot stale so that the next run reloads datasets from the store
+    MarkStale,
+}
+
+impl Command {
+    fn operation(self) -> Operation {
+        match self {
+            Command::Reload { path } => Operation::Reload(path),
+            Command::Verify { path } => Operation::Verify(path),
+            Command::MovePartition { source, target } => Operation::MovePartition(source, target),
+            Command::CopyPartition { source, target } => Operation::CopyPartition(source, target),
+            Command::CopyDataset { source, target } => Operation::CopyDataset(source, target),
+            Command::MoveDataset { source, target } => Operation::MoveDataset(source, target),
+            Command::RemoveDataset { path } => Operation::RemoveDataset(path),
+            Command::Rebalance { path, size } => Operation::Rebalance(path, size),
+            Command::Sample {
+                source,
+                target,
+                percent,
+                seed,
+                partitions,
+            } => Operation::Sample(source, target, partitions, percent, seed),
+            Command::Repartition {
+                source,
+                target,
+                columns,
+            } => Operation::Repartition(source, target, columns),
+            Command::Compress {
+                source,
+                target,
+                codec,
+                gzip_csv,
+                partitions,
+            } => Operation::Compress(
+                source,
+                target,
+                partitions,
+                Compression::new(codec, gzip_csv),
+            ),
+            Command::Generate {
+                path,
+                columns,
+                rows,
+                null_ratio,
+                seed,
+            } => Operation::Generate(path, Generator::new(columns, null_ratio, seed), rows),
+            Command::Convert {
+                source,
+                target,
+                format,
+            } => Operation::Convert(source, target, format),
+            Command::Ls { path, objects } => Operation::Ls(path, objects),
+            Command::LsObjects { path } => Operation::LsObjects(path),
+            Command::SizeOf {
+                path,
+                partitions,
+                detailed,
+            } => Operation::SizeOf(path, partitions, detailed),
+            Command::Exec { .. } => unreachable!("exec is not a single operation"),
+            Command::Resume { .. } => unreachable!("resume is not a single operation"),
+            Command::MarkStale => unreachable!("mark-stale is not an operation"),
+        }
+    }
+}
+
+/// How the jobs of operations are run.
+#[derive(Clone, Copy, Debug)]
+struct RunOptions {
+    dry_run: bool,
+    rollback: bool,
+    json: bool,
+}
+
+fn execute_job(state: &mut State, runtime: &Runtime, job: &dyn Job) -> Result<Execution> {
+    let execution = runtime.execute(state, job.actions(state)?);
+    *state = execution.state.clone();
+    Ok(execution)
+}
 
-fn execute_job(
-    state: &State,
+/// Executes a job as statement `statement` of the journal, resuming it if it was interrupted.
+fn execute_journaled(
+    state: &mut State,
     runtime: &Runtime,
-    path: &DatasetPath,
     job: &dyn Job,
-) -> Result<State> {
-    let view = ListPartitions::new(path.clone(), true);
-    let execution = runtime.execute(&state, job.actions(&state)?);
+    journal: &mut Journal,
+    statement: usize,
+) -> Result<Execution> {
+    journal.begin(statement, state)?;
+
+    let actions = job.actions(state)?;
+    journal.plan(actions.action_keys())?;
 
-    println!("{}", execution);
-    println!("{}", view.render(&execution.state)?);
-    println!("\n---\n");
+    let execution = runtime.execute_journaled(state, actions, journal);
+    *state = execution.state.clone();
+    Ok(execution)
+}
 
+fn into_result(execution: Execution) -> Result<()> {
     match execution.has_errors() {
         true => Err(execution.errors().remove(0)),
-        false => Ok(execution.state),
+        false => Ok(()),
     }
 }
 
-fn example(mut state: State, runtime: &Runtime, path: &DatasetPath) -> Result<State> {
-    let reload = ReloadDataset::new(path.clone());
+fn rollback(
+    state: &mut State,
+    runtime: &Runtime,
+    execution: &mut Execution,
+    options: RunOptions,
+) -> Value {
+    let undo = runtime.rollback(execution);
+    *state = undo.state.clone();
+    if !options.json {
+        println!("rollback:\n{}", undo);
+    }
 
-    let move_partition = MovePartition::new(
-        path.partition_path(&Partition::new("date", "2020-01")),
-        path.partition_path(&Partition::new("date", "2021-01")),
-    );
+    for key in execution.irreversible() {
+        eprintln!("warning: cannot undo {}", key);
+    }
+    let undo_json = undo.to_json();
+    if let Err(error) = into_result(undo) {
+        eprintln!("error: rollback failed: {:?}", error);
+    }
+    undo_json
+}
 
-    let rebalance = RebalanceObjects::new(
-        path.partition_path(&Partition::new("date", "2020-03")),
-        Bytes::new_in_mib(15),
-    );
+fn reload_dependencies(state: &mut State, runtime: &Runtime, operation: &Operation) -> Result<()> {
+    let dependencies = operation.dependencies().into_iter().map(ReloadDataset::new);
+    let targets = operation
+        .targets()
+        .into_iter()
+        .map(ReloadDataset::new_optional);
 
-    state = execute_job(&state, runtime, path, &reload)?;
-    state = execute_job(&state, runtime, path, &move_partition)?;
-    state = execute_job(&state, runtime, path, &rebalance)?;
+    for reload in dependencies.chain(targets) {
+        if !state.contains_dataset(reload.path()) {
+            into_result(execute_job(state, runtime, &reload)?)?;
+        }
+    }
 
-    Ok(state)
+    Ok(())
+}
+
+fn execute_operation(
+    state: &mut State,
+    runtime: &Runtime,
+    operation: &Operation,
+    options: RunOptions,
+    journal: Option<(&mut Journal, usize)>,
+) -> Result<()> {
+    // A dry run of a journal only resumes from its state, it does not record the statement
+    let mut journal = journal.filter(|_| !options.dry_run);
+    reload_dependencies(state, runtime, operation)?;
+    let view = operation.view(state)?;
+
+    let mut output = json!({ "operation": operation.to_string() });
+    let result = match (operation.job(), options.dry_run) {
+        (Some(job), false) => {
+            let mut execution = match &mut journal {
+                Some((journal, statement)) => {
+                    execute_journaled(state, runtime, job.as_ref(), journal, *statement)?
+                }
+                None => execute_job(state, runtime, job.as_ref())?,
+            };
+            match options.json {
+                true => output["execution"] = execution.to_json(),
+                false => println!("{}", execution),
+            }
+
+            if options.rollback && execution.has_errors() {
+                output["rollback"] = rollback(state, runtime, &mut execution, options);
+                if let Some((journal, statement)) = &mut journal {
+                    journal.abort(*statement)?;
+                }
+            }
+            into_result(execution)
+        }
+        (Some(job), true) => {
+            let plan = runtime.plan(state, job.actions(state)?)?;
+            match options.json {
+                true => {
+                    output["plan"] = plan.to_json();
+                    output["state"] = plan.state.to_json()?;
+                }
+                false => println!("{}", plan),
+            }
+            *state = plan.state;
+            Ok(())
+        }
+        (None, _) => Ok(()),
+    };
+
+    if let (Ok(()), Some((journal, statement))) = (&result, journal) {
+        journal.end(statement)?;
+    }
+
+    match options.json {
+        true => output["view"] = view.render_json(state)?,
+        false => println!("{}", view.render(state)?),
+    }
+
+    if options.json {
+        println!("{}", output);
+    }
+    result
+}
+
+fn execute_script(
+    state: &mut State,
+    runtime: &Runtime,
+    script: &Script,
+    keep_going: bool,
+    options: RunOptions,
+    mut journal: Option<&mut Journal>,
+) -> Result<()> {
+    let mut failures = 0;
+
+    for (idx, statement) in script.statements.iter().enumerate() {
+        let journal = match journal.as_deref_mut() {
+            Some(journal) if journal.has_ended(idx) => continue,
+            Some(journal) => {
+                if let Some(resumed) = journal.resumed_state(idx)? {
+                    *state = resumed;
+                }
+                Some((journal, idx))
+            }
+            None => None,
+        };
+
+        if !options.json {
+            println!("{}", statement);
+        }
+
+        let result = execute_operation(state, runtime, &statement.operation, options, journal);
+        if let Err(error) = result {
+            if !keep_going {
+                return Err(error.context(statement.to_string()));
+            }
+            eprintln!("error: {:?}", error.context(statement.to_string()));
+            failures += 1;
+        }
+
+        if !options.json {
+            println!("\n---\n");
+        }
+    }
+
+    match failures {
+        0 => Ok(()),
+        count => Err(anyhow!(
+            "{} of {} statements failed",
+            count,
+            script.statements.len()
+        )),
+    }
+}
+
+/// Runs operations with their journal, which is removed once they all succeed.
+fn with_journal<F>(journal: Option<Journal>, run: F) -> Result<()>
+where
+    F: FnOnce(Option<&mut Journal>) -> Result<()>,
+{
+    let mut journal = journal;
+    let result = run(journal.as_mut());
+
+    match (journal, &result) {
+        (Some(journal), Ok(())) => journal.finish()?,
+        (Some(journal), Err(_)) => eprintln!(
+            "journal kept at {}, resume with: osm --journal {} resume",
+            journal.path().display(),
+            journal.path().display()
+        ),
+        (None, _) => {}
+    }
+    result
 }
 
 fn main() -> Result<()> {
-    let store = FileStore::new(PathBuf::from("/tmp/osm-root"));
+    let opt = Opt::from_args();
+    let workers = opt
+        .workers
+        .or_else(|| {
+            thread::available_parallelism()
+                .ok()
+                .map(|count| count.get())
+        })
+        .unwrap_or(1);
+    let mut router =
+        StoreRouter::new().route_protocol(Protocol::File, Box::new(FileStore::new(opt.root)));
+    if let Some(endpoint) = opt.s3_endpoint {
+        let store = S3Store::new(S3Config::from_env(endpoint, opt.s3_region)?);
+        router = router.route_protocol(Protocol::S3, Box::new(store));
+    }
+    let store: Box<dyn Store> = match opt.faults.is_empty() {
+        true => Box::new(router),
+        false => Box::new(FaultyStore::new(
+            Box::new(router),
+            opt.faults,
+            opt.fault_seed,
+        )),
+    };
+    let retry = RetryPolicy::new(opt.retries, Duration::from_millis(opt.retry_backoff));
+    let runtime = Runtime::new(store, workers).with_retry(retry);
+    let options = RunOptions {
+        dry_run: opt.dry_run,
+        rollback: opt.rollback,
+        json: opt.json,
+    };
+    let snapshot = opt.snapshot.map(Snapshot::new);
 
-    let bucket = Bucket::new(Protocol::File, "example".to_string());
-    let parquet_path = DatasetPath::new(bucket.clone(), PathBuf::from("nyc_taxis"));
-    let csv_path = DatasetPath::new(bucket, PathBuf::from("nyc_taxis_csv"));
+    if let Command::MarkStale = opt.command {
+        return match snapshot {
+            Some(snapshot) => snapshot.mark_stale(),
+            None => Err(anyhow!("mark-stale requires a --snapshot")),
+        };
+    }
 
-    let mut state = State::new();
-    let runtime = Runtime::new(Box::new(store));
+    let mut state = match &snapshot {
+        Some(snapshot) => snapshot.load()?.unwrap_or_default(),
+        None => State::new(),
+    };
 
-    state = example(state, &runtime, &parquet_path)?;
-    state = example(state, &runtime, &csv_path)?;
+    // The snapshot stays stale if the run fails, the store may have been partially modified
+    let snapshot = snapshot.filter(|_| !options.dry_run);
+    if let Some(snapshot) = &snapshot {
+        snapshot.mark_stale()?;
+    }
 
-    Ok(())
+    // Dry runs do not record a journal, they only read the one they resume
+    let journal_path = opt.journal;
+
+    match opt.command {
+        Command::Exec {
+            file,
+            ops,
+            keep_going,
+        } => {
+            let source = match (file, ops) {
+                (Some(file), _) => fs::read_to_string(file)?,
+                (None, Some(ops)) => ops,
+                (None, None) => unreachable!("clap requires a file or --ops"),
+            };
+            let script = source.parse()?;
+            let journal = journal_path
+                .filter(|_| !options.dry_run)
+                .map(|path| Journal::create(path, &source))
+                .transpose()?;
+
+            with_journal(journal, |journal| {
+                execute_script(&mut state, &runtime, &script, keep_going, options, journal)
+            })?
+        }
+        Command::Resume { keep_going } => {
+            let path = journal_path.ok_or_else(|| anyhow!("resume requires a --journal"))?;
+            let journal = match options.dry_run {
+                true => Journal::open_read_only(path)?,
+                false => Journal::open(path)?,
+            };
+            let script = journal.source().parse()?;
+
+            with_journal(Some(journal), |journal| {
+                execute_script(&mut state, &runtime, &script, keep_going, options, journal)
+            })?
+        }
+        command => {
+            let operation = command.operation();
+            let journal = journal_path
+                .filter(|_| !options.dry_run)
+                .map(|path| Journal::create(path, &operation.to_string()))
+                .transpose()?;
+
+            with_journal(journal, |journal| {
+                let journal = journal.map(|journal| (journal, 0));
+                execute_operation(&mut state, &runtime, &operation, options, journal)
+            })?
+        }
+    }
+
+    match snapshot {
+        Some(snapshot) => snapshot.save(&state),
+        None => Ok(()),
+    }
 }
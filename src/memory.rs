@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use anyhow::{Context, Result};
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::reader::{ChunkReader, Length};
+use parquet::file::writer::InMemoryWriteableCursor;
+
+use crate::base::{Bytes, Format, ObjectKey, Partition, ToStdPath};
+use crate::compress::Compression;
+use crate::csv::Csv;
+use crate::generate::Generator;
+use crate::parquet::Parquet;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
+use crate::state::ObjectState;
+use crate::store::{
+    as_err, check_uncompressed, combine_objects, common_format, list_partition_dirs,
+    partition_objects, read_object_state, RebalanceTarget, Store, StoreError,
+};
+
+fn io_err<T>(kind: io::ErrorKind, message: &str) -> Result<T> {
+    as_err(io::Error::new(kind, message))
+}
+
+/// Objects and directories of an in-memory filesystem, keyed by their path from the root.
+#[derive(Default)]
+struct Tree {
+    objects: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl Tree {
+    fn create_dirs(&mut self, path: &Path) {
+        for dir in path.ancestors().filter(|dir| !dir.as_os_str().is_empty()) {
+            self.dirs.insert(dir.to_path_buf());
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    /// Names of the directories and objects right under a directory.
+    fn children(&self, path: &Path) -> Vec<(String, bool)> {
+        let dirs = self.dirs.iter().map(|dir| (dir, true));
+        let objects = self.objects.keys().map(|object| (object, false));
+
+        dirs.chain(objects)
+            .filter(|(child, _)| child.parent() == Some(path))
+            .filter_map(|(child, is_dir)| {
+                let name = child.file_name()?.to_string_lossy().to_string();
+                Some((name, is_dir))
+            })
+            .collect()
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> Result<()> {
+        if !self.is_dir(path) {
+            return io_err(io::ErrorKind::NotFound, "not a directory");
+        }
+        if !self.children(path).is_empty() {
+            return io_err(io::ErrorKind::DirectoryNotEmpty, "directory not empty");
+        }
+
+        self.dirs.remove(path);
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.objects.get(path) {
+            Some(data) => Ok(data.clone()),
+            None => io_err(io::ErrorKind::NotFound, "no such object"),
+        }
+    }
+
+    /// Writes an object into an existing directory, replacing the object if any.
+    fn write(&mut self, path: &Path, data: Vec<u8>) -> Result<()> {
+        match path.parent() {
+            Some(parent) if self.is_dir(parent) => {
+                self.objects.insert(path.to_path_buf(), data);
+                Ok(())
+            }
+            _ => io_err(io::ErrorKind::NotFound, "no such directory"),
+        }
+    }
+}
+
+/// Content of an object read like a file, as a whole by the CSV readers and by ranges by the
+/// Parquet readers.
+struct ObjectReader {
+    cursor: io::Cursor<Vec<u8>>,
+}
+
+impl ObjectReader {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            cursor: io::Cursor::new(data),
+        }
+    }
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.cursor.read(buf)
+    }
+}
+
+impl Seek for ObjectReader {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.cursor.seek(pos)
+    }
+}
+
+impl Length for ObjectReader {
+    fn len(&self) -> u64 {
+        self.cursor.get_ref().len() as u64
+    }
+}
+
+impl ChunkReader for ObjectReader {
+    type T = io::Cursor<Vec<u8>>;
+
+    fn get_read(&self, start: u64, length: usize) -> ParquetResult<Self::T> {
+        let data = self.cursor.get_ref();
+        match data.get(start as usize..start as usize + length) {
+            Some(range) => Ok(io::Cursor::new(range.to_vec())),
+            None => Err(ParquetError::EOF(format!(
+                "range {}+{} out of {} bytes",
+                start,
+                length,
+                data.len()
+            ))),
+        }
+    }
+}
+
+/// Store of objects held in memory, laid out like the directories of a `FileStore` so that
+/// jobs behave the same against both, e.g. for fast tests that do not touch the filesystem.
+#[derive(Default)]
+pub struct MemoryStore {
+    tree: RwLock<Tree>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes an object, creating its partition if needed.
+    pub fn put_object(&self, path: &ObjectPath, data: Vec<u8>) -> Result<()> {
+        let mut tree = self.write_tree();
+        tree.create_dirs(&path.partition_path().std_path());
+        tree.write(&path.std_path(), data)
+            .with_context(|| format!("cannot put object: {}", path))
+    }
+
+    /// Content of an object.
+    pub fn get_object(&self, path: &ObjectPath) -> Result<Vec<u8>> {
+        self.read_tree()
+            .read(&path.std_path())
+            .with_context(|| format!("object not found: {}", path))
+    }
+
+    fn read_tree(&self) -> RwLockReadGuard<'_, Tree> {
+        self.tree.read().expect("memory store lock poisoned")
+    }
+
+    fn write_tree(&self) -> RwLockWriteGuard<'_, Tree> {
+        self.tree.write().expect("memory store lock poisoned")
+    }
+
+    fn read_inputs(&self, paths: &[ObjectPath], operation: &str) -> Result<Vec<ObjectReader>> {
+        let tree = self.read_tree();
+        paths
+            .iter()
+            .map(|path| {
+                let data = tree
+                    .read(&path.std_path())
+                    .with_context(|| format!("{} input object not found: {}", operation, path))?;
+                Ok(ObjectReader::new(data))
+            })
+            .collect()
+    }
+
+    /// Writes the outputs of an operation once it succeeded, and reads their states.
+    fn write_outputs(
+        &self,
+        outputs: Vec<(ObjectPath, InMemoryWriteableCursor)>,
+        create_partitions: bool,
+    ) -> Result<Vec<ObjectState>> {
+        let mut tree = self.write_tree();
+
+        outputs
+            .into_iter()
+            .map(|(path, cursor)| {
+                let data = cursor.data();
+                if create_partitions {
+                    tree.create_dirs(&path.partition_path().std_path());
+                }
+                tree.write(&path.std_path(), data.clone())
+                    .with_context(|| format!("cannot write object: {}", path))?;
+                read_object_state(&path, ObjectReader::new(data))
+            })
+            .collect()
+    }
+}
+
+impl Store for MemoryStore {
+    fn read_object(&self, path: &ObjectPath) -> Result<ObjectState> {
+        let data = self.read_tree().read(&path.std_path())?;
+        read_object_state(path, ObjectReader::new(data))
+    }
+
+    fn open_object(&self, path: &ObjectPath) -> Result<Box<dyn io::Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.get_object(path)?)))
+    }
+
+    fn write_object(&self, path: &ObjectPath, reader: &mut dyn io::Read) -> Result<()> {
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .with_context(|| format!("cannot write object: {}", path))?;
+        self.put_object(path, data)
+    }
+
+    fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let mut tree = self.write_tree();
+        let data = tree
+            .read(&source.std_path())
+            .with_context(|| format!("cannot copy {} to {}", source, target))?;
+
+        tree.create_dirs(&target.partition_path().std_path());
+        tree.write(&target.std_path(), data)
+    }
+
+    fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let mut tree = self.write_tree();
+        let data = match tree.objects.remove(&source.std_path()) {
+            Some(data) => data,
+            None => {
+                return io_err(io::ErrorKind::NotFound, "no such object")
+                    .with_context(|| format!("cannot rename {} to {}", source, target))
+            }
+        };
+
+        tree.create_dirs(&target.partition_path().std_path());
+        tree.write(&target.std_path(), data)
+    }
+
+    fn supports_move(&self) -> bool {
+        true
+    }
+
+    fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<Partition>> {
+        let tree = self.read_tree();
+        let dataset = path.std_path();
+
+        if !tree.is_dir(&dataset) {
+            return as_err(StoreError::MissingDataset(path.clone()));
+        }
+
+        list_partition_dirs(&dataset, PathBuf::new(), &|path| Ok(tree.children(path)))
+    }
+
+    fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectKey>> {
+        let tree = self.read_tree();
+        let partition = path.std_path();
+
+        if !tree.is_dir(&partition) {
+            return io_err(io::ErrorKind::NotFound, "not a directory");
+        }
+
+        Ok(tree
+            .children(&partition)
+            .into_iter()
+            .map(|(name, _)| ObjectKey::new(name))
+            .collect())
+    }
+
+    fn remove_dataset(&self, path: &DatasetPath) -> Result<()> {
+        self.write_tree()
+            .remove_dir(&path.std_path())
+            .with_context(|| format!("dataset to remove not found: {}", path))
+    }
+
+    fn remove_partition(&self, path: &PartitionPath) -> Result<()> {
+        let mut tree = self.write_tree();
+        let mut partition = path.std_path();
+        tree.remove_dir(&partition)
+            .with_context(|| format!("partition to remove not found: {}", path))?;
+
+        // Nested partitions leave their parent directories behind once empty
+        let dataset = path.dataset.std_path();
+        while partition.pop() && partition.starts_with(&dataset) && partition != dataset {
+            // Left as it is when gone or not empty, as on the file store
+            if !tree.is_dir(&partition) || !tree.children(&partition).is_empty() {
+                break;
+            }
+            tree.remove_dir(&partition)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_object(&self, path: &ObjectPath) -> Result<()> {
+        match self.write_tree().objects.remove(&path.std_path()) {
+            Some(_) => Ok(()),
+            None => io_err(io::ErrorKind::NotFound, "no such object")
+                .with_context(|| format!("object to remove not found: {}", path)),
+        }
+    }
+
+    fn rebalance_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        output_paths: &[ObjectPath],
+        target: &RebalanceTarget,
+    ) -> Result<Vec<ObjectState>> {
+        check_uncompressed(input_paths)?;
+
+        let inputs = self.read_inputs(input_paths, "rebalance")?;
+        let outputs = output_paths
+            .iter()
+            .map(|path| (path.clone(), InMemoryWriteableCursor::default()))
+            .collect::<Vec<(ObjectPath, InMemoryWriteableCursor)>>();
+        let writers = outputs
+            .iter()
+            .map(|(_, cursor)| cursor.clone())
+            .collect::<Vec<_>>();
+
+        let cursors = writers.clone();
+        let written = Box::new(move |idx: usize| {
+            // Writes append, so the end of a cursor is its size
+            let end = cursors[idx].clone().seek(io::SeekFrom::End(0))?;
+            Ok(Bytes::new(end as usize))
+        });
+        combine_objects(input_paths, inputs, writers, target, written)?;
+
+        self.write_outputs(outputs, false)
+    }
+
+    fn sample_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        sampler: &Sampler,
+    ) -> Result<ObjectState> {
+        check_uncompressed(std::slice::from_ref(source))?;
+
+        let mut inputs = self.read_inputs(std::slice::from_ref(source), "sample")?;
+        let input = inputs.remove(0);
+        let output = InMemoryWriteableCursor::default();
+
+        let filter = sampler.object_filter(source);
+        match source.infer_format() {
+            Some(Format::Csv) => Csv::filter_object(input, output.clone(), filter),
+            Some(Format::Parquet) => Parquet::filter_object(input, output.clone(), filter),
+            None => as_err(StoreError::CannotInferSchema(source.clone())),
+        }?;
+
+        let mut states = self.write_outputs(vec![(target.clone(), output)], true)?;
+        Ok(states.remove(0))
+    }
+
+    fn repartition_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        target: &DatasetPath,
+        partitioner: &Partitioner,
+    ) -> Result<Vec<(ObjectPath, ObjectState)>> {
+        check_uncompressed(input_paths)?;
+
+        let inputs = self.read_inputs(input_paths, "repartition")?;
+
+        let format = common_format(input_paths)?;
+
+        let mut outputs = vec![];
+        let create_output = |partition: &Partition| {
+            let path = target.object_path(partition, &ObjectKey::new(format!("0.{}", format)));
+            let cursor = InMemoryWriteableCursor::default();
+            outputs.push((path, cursor.clone()));
+            Ok(cursor)
+        };
+
+        partition_objects(&format, inputs, partitioner, create_output)?;
+
+        let paths = outputs
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<ObjectPath>>();
+        let states = self.write_outputs(outputs, true)?;
+        Ok(paths.into_iter().zip(states).collect())
+    }
+
+    fn compress_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        compression: &Compression,
+    ) -> Result<ObjectState> {
+        // Read before anything is written, the source and target can be the same object
+        let mut inputs = self.read_inputs(std::slice::from_ref(source), "compress")?;
+        let input = inputs.remove(0);
+        let output = InMemoryWriteableCursor::default();
+
+        match (source.infer_format(), compression.csv_level) {
+            (Some(Format::Parquet), _) => {
+                Parquet::compress_object(input, output.clone(), compression.codec)
+            }
+            (Some(Format::Csv), Some(level)) if !source.key.is_gzipped() => {
+                Csv::gzip_object(input, output.clone(), level)
+            }
+            _ => as_err(StoreError::CannotCompress(source.clone())),
+        }?;
+
+        let mut states = self.write_outputs(vec![(target.clone(), output)], true)?;
+        Ok(states.remove(0))
+    }
+
+    fn generate_object(
+        &self,
+        path: &ObjectPath,
+        generator: &Generator,
+        rows: usize,
+    ) -> Result<ObjectState> {
+        if path.key.is_gzipped() {
+            return as_err(StoreError::CompressedObject(path.clone()));
+        }
+
+        let output = InMemoryWriteableCursor::default();
+
+        let batches = generator.batches(rows);
+        match path.infer_format() {
+            Some(Format::Csv) => Csv::write_object(output.clone(), batches),
+            Some(Format::Parquet) => {
+                Parquet::write_object(output.clone(), generator.schema(), batches)
+            }
+            None => as_err(StoreError::CannotInferSchema(path.clone())),
+        }?;
+
+        let mut states = self.write_outputs(vec![(path.clone(), output)], true)?;
+        Ok(states.remove(0))
+    }
+
+    fn convert_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<ObjectState> {
+        check_uncompressed(&[source.clone(), target.clone()])?;
+
+        let format = match (source.infer_format(), target.infer_format()) {
+            (Some(source_format), Some(target_format)) if source_format != target_format => {
+                target_format
+            }
+            _ => return as_err(StoreError::CannotConvert(source.clone(), target.clone())),
+        };
+
+        let mut inputs = self.read_inputs(std::slice::from_ref(source), "convert")?;
+        let input = inputs.remove(0);
+        let output = InMemoryWriteableCursor::default();
+
+        match format {
+            Format::Parquet => Csv::convert_object(input, output.clone()),
+            Format::Csv => Parquet::convert_object(input, output.clone()),
+        }?;
+
+        let mut states = self.write_outputs(vec![(target.clone(), output)], true)?;
+        Ok(states.remove(0))
+    }
+}
@@ -1,15 +1,23 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io;
 use std::sync::Arc;
 
 use anyhow::Result;
-use arrow::record_batch::RecordBatchReader;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
 use parquet::arrow::{ArrowReader, ArrowWriter, ParquetFileArrowReader};
 use parquet::file::footer;
 use parquet::file::metadata::ParquetMetaData;
+use parquet::file::properties::WriterProperties;
 use parquet::file::reader::{ChunkReader, SerializedFileReader};
 use parquet::file::writer::ParquetWriter;
 use parquet::schema::types::Type as ParquetType;
 
-use crate::base::Bytes;
+use crate::base::{Bytes, Partition};
+use crate::compress::Codec;
+use crate::csv::Csv;
+use crate::repartition::Partitioner;
 use crate::state::{ObjectState, ParquetFormatState};
 
 pub struct Parquet {}
@@ -21,9 +29,10 @@ impl Parquet {
         let meta = footer::parse_metadata(reader)?;
         let format_state =
             ParquetFormatState::new(Self::parquet_type(&meta), Self::row_count(&meta));
+        // The size of the file rather than of its row groups, which are before compression
         Ok(ObjectState::new_parquet(
             format_state,
-            Self::file_size(&meta),
+            Bytes::new(reader.len() as usize),
         ))
     }
 
@@ -63,19 +72,117 @@ impl Parquet {
         Ok(())
     }
 
-    fn row_count(meta: &ParquetMetaData) -> usize {
-        meta.file_metadata().num_rows() as usize
+    /// Writes the rows of an object kept by `filter` into a new object with the same schema.
+    pub fn filter_object<R: 'static + ChunkReader, W: 'static + ParquetWriter>(
+        reader: R,
+        writer: W,
+        mut filter: impl FnMut(&RecordBatch) -> Result<RecordBatch>,
+    ) -> Result<()> {
+        let file_reader = SerializedFileReader::new(reader)?;
+        let mut arrow_reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+        let record_reader = arrow_reader.get_record_reader(Self::BATCH_SIZE)?;
+        let mut arrow_writer = ArrowWriter::try_new(writer, record_reader.schema(), None)?;
+
+        for batch_result in record_reader {
+            arrow_writer.write(&filter(&batch_result?)?)?;
+        }
+
+        arrow_writer.close()?;
+        Ok(())
+    }
+
+    /// Writes the rows of objects into one new object per partition, created on the first row
+    /// of the partition.
+    pub fn partition_objects<R: 'static + ChunkReader, W: 'static + ParquetWriter>(
+        readers: Vec<R>,
+        partitioner: &Partitioner,
+        mut create_writer: impl FnMut(&Partition) -> Result<W>,
+    ) -> Result<()> {
+        let mut writers: HashMap<Partition, ArrowWriter<W>> = HashMap::new();
+
+        for reader in readers {
+            let file_reader = SerializedFileReader::new(reader)?;
+            let mut arrow_reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+            let record_reader = arrow_reader.get_record_reader(Self::BATCH_SIZE)?;
+
+            for batch_result in record_reader {
+                for (partition, batch) in partitioner.split(&batch_result?)? {
+                    let writer = match writers.entry(partition) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let writer = create_writer(entry.key())?;
+                            entry.insert(ArrowWriter::try_new(writer, batch.schema(), None)?)
+                        }
+                    };
+                    writer.write(&batch)?;
+                }
+            }
+        }
+
+        for (_, mut writer) in writers {
+            writer.close()?;
+        }
+        Ok(())
     }
 
-    fn file_size(meta: &ParquetMetaData) -> Bytes {
-        meta.row_groups()
-            .iter()
-            .map(|group| Bytes::new(group.total_byte_size() as usize))
-            .fold(Bytes::new(0), |acc, bytes| acc + bytes)
+    /// Writes batches of rows of a schema into a new object.
+    pub fn write_object<W: 'static + ParquetWriter>(
+        writer: W,
+        schema: SchemaRef,
+        batches: impl Iterator<Item = Result<RecordBatch>>,
+    ) -> Result<()> {
+        let mut arrow_writer = ArrowWriter::try_new(writer, schema, None)?;
+
+        for batch_result in batches {
+            arrow_writer.write(&batch_result?)?;
+        }
+
+        arrow_writer.close()?;
+        Ok(())
+    }
+
+    /// Writes the rows of an object into a new CSV object.
+    pub fn convert_object<R: 'static + ChunkReader, W: io::Write>(
+        reader: R,
+        writer: W,
+    ) -> Result<()> {
+        let file_reader = SerializedFileReader::new(reader)?;
+        let mut arrow_reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+        let record_reader = arrow_reader.get_record_reader(Self::BATCH_SIZE)?;
+
+        Csv::write_object(writer, record_reader.map(|batch| Ok(batch?)))
+    }
+
+    /// Rewrites the rows of an object into a new object with pages compressed by `codec`.
+    pub fn compress_object<R: 'static + ChunkReader, W: 'static + ParquetWriter>(
+        reader: R,
+        writer: W,
+        codec: Codec,
+    ) -> Result<()> {
+        let file_reader = SerializedFileReader::new(reader)?;
+        let mut arrow_reader = ParquetFileArrowReader::new(Arc::new(file_reader));
+        let record_reader = arrow_reader.get_record_reader(Self::BATCH_SIZE)?;
+
+        let properties = WriterProperties::builder()
+            .set_compression(codec.parquet_compression())
+            .build();
+        let mut arrow_writer =
+            ArrowWriter::try_new(writer, record_reader.schema(), Some(properties))?;
+
+        for batch_result in record_reader {
+            arrow_writer.write(&batch_result?)?;
+        }
+
+        arrow_writer.close()?;
+        Ok(())
+    }
+
+    fn row_count(meta: &ParquetMetaData) -> usize {
+        meta.file_metadata().num_rows() as usize
     }
 
     fn parquet_type(meta: &ParquetMetaData) -> ParquetType {
-        // FIXME: Handle empty files
-        meta.row_groups()[0].schema_descr().root_schema().clone()
+        // Read from the file rather than a row group, empty objects have no row groups
+        meta.file_metadata().schema().clone()
     }
 }
@@ -1,7 +1,8 @@
 use std::fmt;
 use std::path::{PathBuf};
+use std::str::FromStr;
 
-use crate::base::{Bucket, Format, ObjectKey, Partition, ToStdPath};
+use crate::base::{Bucket, Format, ObjectKey, ParseError, Partition, ToStdPath};
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct DatasetPath {
@@ -14,6 +15,10 @@ impl DatasetPath {
         DatasetPath { bucket, path }
     }
 
+    pub fn bucket(&self) -> &Bucket {
+        &self.bucket
+    }
+
     pub fn partition_path(&self, partition: &Partition) -> PartitionPath {
         PartitionPath {
             dataset: self.clone(),
@@ -47,6 +52,29 @@ impl fmt::Display for DatasetPath {
     }
 }
 
+impl FromStr for DatasetPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_end_matches('/');
+        let bucket_idx = s
+            .find("://")
+            .map(|idx| idx + 3)
+            .ok_or_else(|| ParseError::InvalidPath(s.to_string()))?;
+        let path_idx = s[bucket_idx..]
+            .find('/')
+            .map(|idx| bucket_idx + idx)
+            .ok_or_else(|| ParseError::InvalidPath(s.to_string()))?;
+
+        let path = s[path_idx..].trim_start_matches('/');
+        if path.is_empty() || path.contains('=') {
+            return Err(ParseError::InvalidPath(s.to_string()));
+        }
+
+        Ok(DatasetPath::new(s[0..path_idx].parse()?, PathBuf::from(path)))
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct PartitionPath {
     pub dataset: DatasetPath,
@@ -80,6 +108,28 @@ impl fmt::Display for PartitionPath {
     }
 }
 
+impl FromStr for PartitionPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split_idx = s
+            .match_indices('/')
+            .map(|(idx, _)| idx)
+            .find(|idx| {
+                s[idx + 1..]
+                    .split('/')
+                    .next()
+                    .is_some_and(|segment| segment.contains('='))
+            })
+            .ok_or_else(|| ParseError::InvalidPath(s.to_string()))?;
+
+        Ok(PartitionPath::new(
+            s[0..split_idx].parse()?,
+            s[split_idx + 1..].parse()?,
+        ))
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct ObjectPath {
     partition: PartitionPath,
@@ -129,3 +179,17 @@ impl fmt::Display for ObjectPath {
         write!(f, "{}/{}", self.partition, self.key)
     }
 }
+
+impl FromStr for ObjectPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rfind('/') {
+            Some(idx) if idx < s.len() - 1 => {
+                let partition: PartitionPath = s[0..idx].parse()?;
+                Ok(partition.object_path(&ObjectKey::new(s[idx + 1..].to_string())))
+            }
+            _ => Err(ParseError::InvalidPath(s.to_string())),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use arrow::array::{as_primitive_array, ArrayRef, BooleanArray};
+use arrow::compute::filter_record_batch;
+use arrow::datatypes::{DataType, Date32Type, Date64Type};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use thiserror::Error;
+
+use crate::base::Partition;
+
+#[derive(Error, Debug)]
+pub enum RepartitionError {
+    #[error("Missing partition column: {0}")]
+    MissingColumn(String),
+}
+
+/// Groups rows by the values of some of their columns, every group being a `key=value`
+/// partition with one level per column. The columns are kept in the rows.
+#[derive(Clone, Debug)]
+pub struct Partitioner {
+    keys: Vec<String>,
+}
+
+impl Partitioner {
+    /// Value of the partition of rows where the column is null.
+    const NULL_VALUE: &'static str = "__null__";
+    /// Value of the partition of rows where the column is an empty string, `key=` is not a
+    /// valid partition.
+    const EMPTY_VALUE: &'static str = "__empty__";
+
+    pub fn new(keys: Vec<String>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Splits a batch into one batch per partition, sorted by partition.
+    pub fn split(&self, batch: &RecordBatch) -> Result<Vec<(Partition, RecordBatch)>> {
+        let columns = self
+            .keys
+            .iter()
+            .map(|key| match batch.schema().index_of(key) {
+                Ok(idx) => Ok(batch.column(idx).clone()),
+                Err(_) => Err(RepartitionError::MissingColumn(key.clone()).into()),
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+
+        let mut rows_by_partition: BTreeMap<Partition, Vec<bool>> = BTreeMap::new();
+        for row in 0..batch.num_rows() {
+            let partition = self.row_partition(&columns, row)?;
+            rows_by_partition
+                .entry(partition)
+                .or_insert_with(|| vec![false; batch.num_rows()])[row] = true;
+        }
+
+        rows_by_partition
+            .into_iter()
+            .map(|(partition, rows)| {
+                let mask = BooleanArray::from(rows);
+                Ok((partition, filter_record_batch(batch, &mask)?))
+            })
+            .collect()
+    }
+
+    fn row_partition(&self, columns: &[ArrayRef], row: usize) -> Result<Partition> {
+        let mut partition: Option<Partition> = None;
+
+        for (key, column) in self.keys.iter().zip(columns) {
+            let value = match column.is_null(row) {
+                true => Self::NULL_VALUE.to_string(),
+                // Separators would turn a value into nested directories
+                false => match Self::value_to_string(column, row)?.replace('/', "-") {
+                    value if value.is_empty() => Self::EMPTY_VALUE.to_string(),
+                    value => value,
+                },
+            };
+
+            partition = Some(match partition {
+                Some(partition) => partition.push(key.clone(), value),
+                None => Partition::new(key.clone(), value),
+            });
+        }
+
+        Ok(partition.expect("a partitioner has at least one key"))
+    }
+
+    /// Dates are written as `YYYY-MM-DD` rather than as a number of days or milliseconds.
+    fn value_to_string(column: &ArrayRef, row: usize) -> Result<String> {
+        let date = match column.data_type() {
+            DataType::Date32(_) => as_primitive_array::<Date32Type>(column).value_as_date(row),
+            DataType::Date64(_) => as_primitive_array::<Date64Type>(column).value_as_date(row),
+            _ => None,
+        };
+
+        match date {
+            Some(date) => Ok(date.to_string()),
+            None => Ok(array_value_to_string(column, row)?),
+        }
+    }
+}
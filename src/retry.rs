@@ -0,0 +1,69 @@
+use std::io;
+use std::time::Duration;
+
+use anyhow::Error;
+
+use crate::fault::FaultError;
+use crate::s3::S3Error;
+use crate::store::{is_transient_io, StoreError};
+
+/// How many times an action failing with a retryable error is tried again, waiting twice as
+/// long before every attempt, from `backoff` up to `MAX_BACKOFF`.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    retries: usize,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    pub fn new(retries: usize, backoff: Duration) -> Self {
+        Self { retries, backoff }
+    }
+
+    /// Actions are tried once.
+    pub fn none() -> Self {
+        Self::new(0, Duration::from_millis(0))
+    }
+
+    /// Whether an action that failed its `attempts` first attempts with `error` is tried again.
+    pub fn should_retry(&self, attempts: usize, error: &Error) -> bool {
+        attempts <= self.retries && is_retryable(error)
+    }
+
+    /// Wait before the attempt following the `attempts` first ones.
+    pub fn backoff(&self, attempts: usize) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1) as u32);
+        self.backoff
+            .checked_mul(factor)
+            .map_or(Self::MAX_BACKOFF, |backoff| backoff.min(Self::MAX_BACKOFF))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Whether an error is transient, going down its chain of causes to the first one that is a
+/// known store error. Unknown errors, e.g. drifts or invalid objects, are fatal.
+pub fn is_retryable(error: &Error) -> bool {
+    for cause in error.chain() {
+        if let Some(error) = cause.downcast_ref::<StoreError>() {
+            return error.is_retryable();
+        }
+        if let Some(error) = cause.downcast_ref::<S3Error>() {
+            return error.is_retryable();
+        }
+        if let Some(error) = cause.downcast_ref::<FaultError>() {
+            return error.is_retryable();
+        }
+        if let Some(error) = cause.downcast_ref::<io::Error>() {
+            return is_transient_io(error);
+        }
+    }
+
+    false
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use thiserror::Error;
+
+use crate::base::{Bucket, ObjectKey, Partition, Protocol};
+use crate::compress::Compression;
+use crate::generate::Generator;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
+use crate::state::ObjectState;
+use crate::store::{RebalanceTarget, Scratch, Store};
+
+#[derive(Error, Debug)]
+pub enum RouterError {
+    #[error("No store for bucket: {0}")]
+    MissingStore(Bucket),
+}
+
+/// Store dispatching every operation to the store of the bucket of its paths, a store routed by
+/// bucket taking precedence over the one of its protocol. Objects are copied between stores
+/// through their content, and rewrites from one store to another run on a local copy of their
+/// inputs.
+#[derive(Default)]
+pub struct StoreRouter {
+    stores: Vec<Box<dyn Store>>,
+    protocols: HashMap<Protocol, usize>,
+    buckets: HashMap<Bucket, usize>,
+    scratch_count: AtomicUsize,
+}
+
+impl StoreRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes the buckets of a protocol to a store, unless they have a store of their own.
+    /// Routing a protocol again replaces its store.
+    pub fn route_protocol(mut self, protocol: Protocol, store: Box<dyn Store>) -> Self {
+        let idx = self.insert_store(self.protocols.get(&protocol).copied(), store);
+        self.protocols.insert(protocol, idx);
+        self
+    }
+
+    /// Routing a bucket again replaces its store.
+    pub fn route_bucket(mut self, bucket: Bucket, store: Box<dyn Store>) -> Self {
+        let idx = self.insert_store(self.buckets.get(&bucket).copied(), store);
+        self.buckets.insert(bucket, idx);
+        self
+    }
+
+    /// Index of a store, replacing the store at `idx` if any. Every route has a store of its
+    /// own, so that no other route uses a replaced store.
+    fn insert_store(&mut self, idx: Option<usize>, store: Box<dyn Store>) -> usize {
+        match idx {
+            Some(idx) => {
+                self.stores[idx] = store;
+                idx
+            }
+            None => {
+                self.stores.push(store);
+                self.stores.len() - 1
+            }
+        }
+    }
+
+    /// Index of the store of a bucket.
+    fn route(&self, bucket: &Bucket) -> Result<usize> {
+        match self.buckets.get(bucket) {
+            Some(idx) => Ok(*idx),
+            None => match self.protocols.get(&bucket.protocol) {
+                Some(idx) => Ok(*idx),
+                None => Err(RouterError::MissingStore(bucket.clone()).into()),
+            },
+        }
+    }
+
+    fn dataset_store(&self, path: &DatasetPath) -> Result<(usize, &dyn Store)> {
+        let idx = self.route(path.bucket())?;
+        Ok((idx, self.stores[idx].as_ref()))
+    }
+
+    fn object_store(&self, path: &ObjectPath) -> Result<(usize, &dyn Store)> {
+        self.dataset_store(path.dataset_path())
+    }
+
+    /// Copies the content of an object into an object of another store.
+    fn transfer(
+        source_store: &dyn Store,
+        source: &ObjectPath,
+        target_store: &dyn Store,
+        target: &ObjectPath,
+    ) -> Result<()> {
+        let mut reader = source_store.open_object(source)?;
+        target_store
+            .write_object(target, &mut reader)
+            .with_context(|| format!("cannot copy {} to {}", source, target))
+    }
+
+    /// Local copy of the inputs of a rewrite into another store.
+    fn stage(&self, paths: &[ObjectPath]) -> Result<Scratch> {
+        let scratch = Scratch::new("router", self.scratch_count.fetch_add(1, Ordering::SeqCst))?;
+        for path in paths {
+            let (_, store) = self.object_store(path)?;
+            Self::transfer(store, path, &scratch.store, path)?;
+        }
+        Ok(scratch)
+    }
+
+    /// Copies the outputs of a staged rewrite into their store.
+    fn unstage(&self, scratch: &Scratch, paths: &[ObjectPath]) -> Result<()> {
+        for path in paths {
+            let (_, store) = self.object_store(path)?;
+            Self::transfer(&scratch.store, path, store, path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Store for StoreRouter {
+    fn read_object(&self, path: &ObjectPath) -> Result<ObjectState> {
+        self.object_store(path)?.1.read_object(path)
+    }
+
+    fn open_object(&self, path: &ObjectPath) -> Result<Box<dyn io::Read + Send>> {
+        self.object_store(path)?.1.open_object(path)
+    }
+
+    fn write_object(&self, path: &ObjectPath, reader: &mut dyn io::Read) -> Result<()> {
+        self.object_store(path)?.1.write_object(path, reader)
+    }
+
+    fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let (source_idx, source_store) = self.object_store(source)?;
+        let (target_idx, target_store) = self.object_store(target)?;
+
+        match source_idx == target_idx {
+            true => source_store.copy_object(source, target),
+            false => Self::transfer(source_store, source, target_store, target),
+        }
+    }
+
+    fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let (source_idx, source_store) = self.object_store(source)?;
+        let (target_idx, target_store) = self.object_store(target)?;
+
+        match source_idx == target_idx {
+            true => source_store.move_object(source, target),
+            false => {
+                Self::transfer(source_store, source, target_store, target)?;
+                source_store.remove_object(source)
+            }
+        }
+    }
+
+    /// Moves are split by the runtime unless every store supports them.
+    fn supports_move(&self) -> bool {
+        self.stores.iter().all(|store| store.supports_move())
+    }
+
+    fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<Partition>> {
+        self.dataset_store(path)?.1.list_partitions(path)
+    }
+
+    fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectKey>> {
+        self.dataset_store(&path.dataset)?.1.list_objects(path)
+    }
+
+    fn remove_dataset(&self, path: &DatasetPath) -> Result<()> {
+        self.dataset_store(path)?.1.remove_dataset(path)
+    }
+
+    fn remove_partition(&self, path: &PartitionPath) -> Result<()> {
+        self.dataset_store(&path.dataset)?.1.remove_partition(path)
+    }
+
+    fn remove_object(&self, path: &ObjectPath) -> Result<()> {
+        self.object_store(path)?.1.remove_object(path)
+    }
+
+    /// Inputs and outputs are in the same partition, and so in the same store.
+    fn rebalance_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        output_paths: &[ObjectPath],
+        target: &RebalanceTarget,
+    ) -> Result<Vec<ObjectState>> {
+        self.object_store(&input_paths[0])?
+            .1
+            .rebalance_objects(input_paths, output_paths, target)
+    }
+
+    fn sample_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        sampler: &Sampler,
+    ) -> Result<ObjectState> {
+        let (source_idx, source_store) = self.object_store(source)?;
+        let (target_idx, _) = self.object_store(target)?;
+
+        if source_idx == target_idx {
+            return source_store.sample_object(source, target, sampler);
+        }
+
+        let scratch = self.stage(std::slice::from_ref(source))?;
+        let state = scratch.store.sample_object(source, target, sampler)?;
+        self.unstage(&scratch, std::slice::from_ref(target))?;
+        Ok(state)
+    }
+
+    fn repartition_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        target: &DatasetPath,
+        partitioner: &Partitioner,
+    ) -> Result<Vec<(ObjectPath, ObjectState)>> {
+        let (source_idx, source_store) = self.object_store(&input_paths[0])?;
+        let (target_idx, _) = self.dataset_store(target)?;
+
+        if source_idx == target_idx {
+            return source_store.repartition_objects(input_paths, target, partitioner);
+        }
+
+        let scratch = self.stage(input_paths)?;
+        let outputs = scratch
+            .store
+            .repartition_objects(input_paths, target, partitioner)?;
+        let output_paths = outputs
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<ObjectPath>>();
+        self.unstage(&scratch, &output_paths)?;
+        Ok(outputs)
+    }
+
+    fn compress_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        compression: &Compression,
+    ) -> Result<ObjectState> {
+        let (source_idx, source_store) = self.object_store(source)?;
+        let (target_idx, _) = self.object_store(target)?;
+
+        if source_idx == target_idx {
+            return source_store.compress_object(source, target, compression);
+        }
+
+        let scratch = self.stage(std::slice::from_ref(source))?;
+        let state = scratch.store.compress_object(source, target, compression)?;
+        self.unstage(&scratch, std::slice::from_ref(target))?;
+        Ok(state)
+    }
+
+    fn generate_object(
+        &self,
+        path: &ObjectPath,
+        generator: &Generator,
+        rows: usize,
+    ) -> Result<ObjectState> {
+        self.object_store(path)?
+            .1
+            .generate_object(path, generator, rows)
+    }
+
+    fn convert_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<ObjectState> {
+        let (source_idx, source_store) = self.object_store(source)?;
+        let (target_idx, _) = self.object_store(target)?;
+
+        if source_idx == target_idx {
+            return source_store.convert_object(source, target);
+        }
+
+        let scratch = self.stage(std::slice::from_ref(source))?;
+        let state = scratch.store.convert_object(source, target)?;
+        self.unstage(&scratch, std::slice::from_ref(target))?;
+        Ok(state)
+    }
+}
@@ -1,8 +1,19 @@
+use std::collections::HashSet;
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
-use anyhow::Error;
+use anyhow::{Context, Error, Result};
+use serde_json::{json, Value};
 
-use crate::action::{ActionTree, Keys};
+use crate::action::{
+    Action, ActionTree, Actions, Keys, ReloadDatasetAction, ReloadPartitionAction,
+};
+use crate::drift::DriftError;
+use crate::effect::{self, EffectKind, Resource};
+use crate::journal::Journal;
+use crate::retry::RetryPolicy;
 use crate::state::State;
 use crate::store::Store;
 
@@ -10,11 +21,49 @@ pub struct Execution {
     pub state: State,
     passed: Vec<String>,
     failed: Vec<(String, Error)>,
+    undo: Vec<Actions>,
+    irreversible: Vec<String>,
+    attempts: Vec<(String, usize)>,
 }
 
 impl Execution {
-    fn new(state: State, passed: Vec<String>, failed: Vec<(String, Error)>) -> Self {
-        Self { state, passed, failed }
+    fn new(state: State) -> Self {
+        Self {
+            state,
+            passed: vec![],
+            failed: vec![],
+            undo: vec![],
+            irreversible: vec![],
+            attempts: vec![],
+        }
+    }
+
+    fn pass(&mut self, action: &dyn Action, base_state: &State, new_state: &State) {
+        match action.inverse(base_state) {
+            Some(inverse) => self.undo.push(inverse),
+            None => self.irreversible.push(action.key()),
+        }
+        self.passed.push(action.key());
+        self.state = self.state.merge(new_state);
+    }
+
+    /// Keys of the passed actions that cannot be undone by a rollback.
+    pub fn irreversible(&self) -> &[String] {
+        &self.irreversible
+    }
+
+    /// Keys of the actions executed against the store and how many times they were tried.
+    pub fn attempts(&self) -> &[(String, usize)] {
+        &self.attempts
+    }
+
+    /// Actions tried more than once, whether they passed or not.
+    fn retried(&self) -> Vec<(&str, usize)> {
+        self.attempts
+            .iter()
+            .filter(|(_, attempts)| *attempts > 1)
+            .map(|(key, attempts)| (key.as_str(), *attempts))
+            .collect()
     }
 
     pub fn has_errors(&self) -> bool {
@@ -24,55 +73,357 @@ impl Execution {
     pub fn errors(self) -> Vec<Error> {
         self.failed.into_iter().map(|(_, error)| error).collect()
     }
+
+    /// Passed and failed action keys, every failure with its chain of errors and the drift of
+    /// its dataset if it failed verifying one.
+    pub fn to_json(&self) -> Value {
+        let failed = self
+            .failed
+            .iter()
+            .map(|(key, error)| {
+                let errors = error
+                    .chain()
+                    .map(|cause| cause.to_string())
+                    .collect::<Vec<String>>();
+                let mut failure = json!({"action": key, "errors": errors});
+                if let Some(DriftError::Drifted(path, drift)) = error.downcast_ref() {
+                    failure["drift"] =
+                        json!({"dataset": path.to_string(), "diff": drift.to_json()});
+                }
+                failure
+            })
+            .collect::<Vec<Value>>();
+        let retried = self
+            .retried()
+            .into_iter()
+            .map(|(key, attempts)| json!({"action": key, "attempts": attempts}))
+            .collect::<Vec<Value>>();
+
+        json!({
+            "passed": self.passed,
+            "failed": failed,
+            "irreversible": self.irreversible,
+            "retried": retried,
+        })
+    }
 }
 
 impl fmt::Display for Execution {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "passed: {:#?}", self.passed)?;
-        writeln!(f, "failed: {:#?}", self.failed)
+        writeln!(f, "failed: {:#?}", self.failed)?;
+
+        let retried = self.retried();
+        if !retried.is_empty() {
+            writeln!(f, "retried: {:#?}", retried)?;
+        }
+        Ok(())
+    }
+}
+
+/// Predicted outcome of an ActionTree: the keys of the actions of every layer and the state
+/// left once they have all run.
+pub struct Plan {
+    pub state: State,
+    layers: Vec<Vec<String>>,
+}
+
+impl Plan {
+    pub fn to_json(&self) -> Value {
+        json!({ "layers": self.layers })
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, layer) in self.layers.iter().enumerate() {
+            writeln!(f, "layer {}:", idx + 1)?;
+            for key in layer {
+                writeln!(f, "  - {}", key)?;
+            }
+        }
+        Ok(())
     }
 }
 
 pub struct Runtime {
     store: Box<dyn Store>,
+    workers: usize,
+    retry: RetryPolicy,
 }
 
 impl Runtime {
-    pub fn new(store: Box<dyn Store>) -> Self {
-        Runtime { store }
+    pub fn new(store: Box<dyn Store>, workers: usize) -> Self {
+        Runtime {
+            store,
+            workers: workers.max(1),
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    /// Tries actions failing with transient store errors again, actions are tried once otherwise.
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Runtime { retry, ..self }
+    }
+
+    fn lower(&self, actions: ActionTree) -> ActionTree {
+        match self.store.supports_move() {
+            true => actions,
+            false => actions.split_moves(),
+        }
     }
 
     pub fn execute(&self, state: &State, actions: ActionTree) -> Execution {
-        let mut passed = vec![];
-        let mut failed = vec![];
+        self.run(state, actions, None)
+    }
 
-        let mut current_state = state.clone();
+    /// Executes the tree while recording completed actions in the journal. Actions that wrote to
+    /// the store before the journal was interrupted only have their state transitions applied.
+    pub fn execute_journaled(
+        &self,
+        state: &State,
+        actions: ActionTree,
+        journal: &Journal,
+    ) -> Execution {
+        self.run(state, actions, Some(journal))
+    }
+
+    fn run(&self, state: &State, actions: ActionTree, journal: Option<&Journal>) -> Execution {
+        let actions = self.lower(actions);
+
+        let mut execution = Execution::new(state.clone());
         let mut completed = Keys::new();
 
         while completed.len() != actions.size() {
             let mut error_count = 0;
 
-            for (key, actions) in actions.next_batch(&completed) {
-                for action in actions {
-                    match action.execute(self.store.as_ref(), &current_state) {
-                        Ok(new_state) => {
-                            passed.push(action.key());
-                            current_state = new_state;
-                        }
-                        Err(error) => {
-                            error_count += 1;
-                            failed.push((action.key(), error))
-                        }
+            let mut batch = actions.next_batch(&completed);
+            batch.sort_by_key(|(key, _)| *key);
+
+            let (resumed, batch_actions): (Vec<&dyn Action>, Vec<&dyn Action>) = batch
+                .iter()
+                .flat_map(|(_, actions)| actions.iter().copied())
+                .partition(|action| self.is_resumed(journal, *action));
+
+            for action in resumed {
+                let base_state = execution.state.checkpoint();
+                match self.resume(action, &base_state) {
+                    Ok(new_state) => execution.pass(action, &base_state, &new_state),
+                    Err(error) => {
+                        execution.failed.push((action.key(), error));
+                        return execution;
+                    }
+                }
+            }
+
+            if let Err(error) = effect::check_conflicts(&batch_actions) {
+                execution.failed.push(("schedule".to_string(), error));
+                return execution;
+            }
+
+            // Every action of the batch runs from the same state, their results are merged in
+            // the order of the batch so that the final state does not depend on scheduling.
+            let base_state = execution.state.checkpoint();
+            let results = self.execute_batch(&base_state, &batch_actions, journal);
+
+            for (action, (result, attempts)) in batch_actions.iter().zip(results) {
+                execution.attempts.push((action.key(), attempts));
+                match result {
+                    Ok(new_state) => execution.pass(*action, &base_state, &new_state),
+                    Err(error) => {
+                        error_count += 1;
+                        execution.failed.push((action.key(), error))
                     }
                 }
-                completed.insert(key);
             }
 
+            completed.extend(batch.iter().map(|(key, _)| *key));
+
             if error_count > 0 {
-                return Execution::new(current_state, passed, failed);
+                return execution;
             }
         }
 
-        Execution::new(current_state, passed, failed)
+        execution
+    }
+
+    /// Undoes the passed actions of an execution, latest first, from the state it left. Actions
+    /// that cannot be undone are listed by `Execution::irreversible` and stay as they are.
+    pub fn rollback(&self, execution: &mut Execution) -> Execution {
+        let mut undo = std::mem::take(&mut execution.undo)
+            .into_iter()
+            .rev()
+            .flatten()
+            .collect::<Actions>();
+
+        // Actions may share an inverse, such as the removal of the partition they created, it
+        // only runs once after all the other inverses it conflicts with.
+        let mut keys = HashSet::new();
+        undo.reverse();
+        undo.retain(|action| keys.insert(action.key()));
+        undo.reverse();
+
+        self.execute(&execution.state, ActionTree::from_effects(undo))
+    }
+
+    /// Walks the tree in the same order as `execute`, only predicting the state transitions of
+    /// the actions. The store is never written to.
+    pub fn plan(&self, state: &State, actions: ActionTree) -> Result<Plan> {
+        let actions = self.lower(actions);
+
+        let mut layers = vec![];
+        let mut current_state = state.clone();
+        let mut completed = Keys::new();
+
+        while completed.len() != actions.size() {
+            let mut batch = actions.next_batch(&completed);
+            batch.sort_by_key(|(key, _)| *key);
+
+            let batch_actions = batch
+                .iter()
+                .flat_map(|(_, actions)| actions.iter().copied())
+                .collect::<Vec<&dyn Action>>();
+
+            effect::check_conflicts(&batch_actions)?;
+
+            for action in &batch_actions {
+                current_state = action
+                    .predict(self.store.as_ref(), &current_state)
+                    .with_context(|| format!("Planning {}", action.key()))?;
+            }
+
+            layers.push(batch_actions.iter().map(|action| action.key()).collect());
+            completed.extend(batch.iter().map(|(key, _)| *key));
+        }
+
+        Ok(Plan {
+            state: current_state,
+            layers,
+        })
+    }
+
+    /// Whether an action wrote to the store before the journal was interrupted, in which case it
+    /// must not run again. An action interrupted while running is done if everything it removes
+    /// is gone from the store, otherwise it runs again like actions that only read.
+    fn is_resumed(&self, journal: Option<&Journal>, action: &dyn Action) -> bool {
+        let journal = match journal {
+            Some(journal) => journal,
+            None => return false,
+        };
+
+        let key = action.key();
+        let effects = action.effects();
+        let removes = effects
+            .iter()
+            .filter(|effect| effect.kind == EffectKind::Remove)
+            .collect::<Vec<_>>();
+
+        let creates = effects.iter().any(|effect| effect.kind == EffectKind::Create);
+        if !creates && removes.is_empty() {
+            return false;
+        }
+
+        journal.is_completed(&key)
+            || journal.is_interrupted(&key)
+                && !removes.is_empty()
+                && removes.iter().all(|effect| !self.exists(&effect.resource))
+    }
+
+    /// State after an action that ran before the journal was interrupted. What it created is
+    /// reloaded from the store, its prediction being only an estimate, e.g. of sizes.
+    fn resume(&self, action: &dyn Action, state: &State) -> Result<State> {
+        let store = self.store.as_ref();
+        let mut new_state = action.apply(state)?;
+
+        for effect in action.effects() {
+            if effect.kind != EffectKind::Create {
+                continue;
+            }
+            new_state = match effect.resource {
+                Resource::Dataset(path) => {
+                    ReloadDatasetAction::new_optional(path).execute(store, &new_state)?
+                }
+                Resource::Partition(path) => {
+                    ReloadPartitionAction::new(path).execute(store, &new_state)?
+                }
+                Resource::Object(path) => {
+                    new_state.insert_object(&path, store.read_object(&path)?)?
+                }
+            };
+        }
+
+        Ok(new_state)
+    }
+
+    fn exists(&self, resource: &Resource) -> bool {
+        match resource {
+            Resource::Dataset(path) => self.store.list_partitions(path).is_ok(),
+            Resource::Partition(path) => self
+                .store
+                .list_partitions(&path.dataset)
+                .is_ok_and(|partitions| partitions.contains(&path.partition)),
+            Resource::Object(path) => self
+                .store
+                .list_objects(path.partition_path())
+                .is_ok_and(|keys| keys.contains(&path.key)),
+        }
+    }
+
+    /// Executes an action, again after a backoff as long as it fails with a retryable error.
+    /// Returns its result and how many times it was tried.
+    fn execute_action(&self, action: &dyn Action, state: &State) -> (Result<State>, usize) {
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            match action.execute(self.store.as_ref(), state) {
+                Err(error) if self.retry.should_retry(attempts, &error) => {
+                    thread::sleep(self.retry.backoff(attempts));
+                }
+                result => return (result, attempts),
+            }
+        }
+    }
+
+    fn execute_batch(
+        &self,
+        state: &State,
+        actions: &[&dyn Action],
+        journal: Option<&Journal>,
+    ) -> Vec<(Result<State>, usize)> {
+        let next_idx = AtomicUsize::new(0);
+        let results = Mutex::new(actions.iter().map(|_| None).collect::<Vec<_>>());
+
+        thread::scope(|scope| {
+            for _ in 0..self.workers.min(actions.len()) {
+                scope.spawn(|| loop {
+                    let idx = next_idx.fetch_add(1, Ordering::SeqCst);
+                    if idx >= actions.len() {
+                        break;
+                    }
+
+                    let action = actions[idx];
+                    let (result, attempts) = match journal.map(|j| j.start(&action.key())) {
+                        Some(Err(error)) => (Err(error), 0),
+                        _ => self.execute_action(action, state),
+                    };
+                    let result = match journal {
+                        Some(journal) => result.and_then(|new_state| {
+                            journal.complete(&action.key())?;
+                            Ok(new_state)
+                        }),
+                        None => result,
+                    };
+                    results.lock().unwrap()[idx] = Some((result, attempts));
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every action of the batch is executed"))
+            .collect()
     }
 }
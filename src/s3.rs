@@ -0,0 +1,920 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::reader::{ChunkReader, Length};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::base::{Bytes, Format, ObjectKey, Partition, ToStdPath};
+use crate::compress::Compression;
+use crate::csv::Csv;
+use crate::generate::Generator;
+use crate::parquet::Parquet;
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
+use crate::state::ObjectState;
+use crate::store::{RebalanceTarget, Scratch, Store, StoreError};
+
+#[derive(Error, Debug)]
+pub enum S3Error {
+    #[error("Missing S3 credentials: {0} is not set")]
+    MissingCredentials(&'static str),
+
+    #[error("Invalid S3 endpoint: {0}")]
+    InvalidEndpoint(String),
+
+    #[error("S3 {0} responded {1}: {2}")]
+    Status(String, u16, String),
+
+    #[error("S3 {0} failed: {1}")]
+    Transport(String, String, ureq::ErrorKind),
+
+    #[error("Invalid S3 response to {0}: {1}")]
+    InvalidResponse(String, String),
+
+    #[error("Cannot remove non-empty prefix: {0}")]
+    NotEmpty(String),
+}
+
+impl S3Error {
+    /// Whether the request may succeed if sent again: it timed out, was throttled, failed on
+    /// the server side or the connection to it failed. Requests to a host that cannot be
+    /// resolved or addressed are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            S3Error::Status(_, status, _) => matches!(status, 408 | 429 | 500..=599),
+            S3Error::Transport(_, _, kind) => {
+                matches!(
+                    kind,
+                    ureq::ErrorKind::Io | ureq::ErrorKind::ConnectionFailed
+                )
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Endpoint and credentials of an S3-compatible object store, buckets are addressed by path
+/// (`<endpoint>/<bucket>/<key>`) which local stand-ins support as well.
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    endpoint: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Config {
+    pub fn new(endpoint: String, region: String, access_key: String, secret_key: String) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region,
+            access_key,
+            secret_key,
+        }
+    }
+
+    /// Reads the credentials from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+    pub fn from_env(endpoint: String, region: String) -> Result<Self> {
+        let access_key = env::var("AWS_ACCESS_KEY_ID")
+            .map_err(|_| S3Error::MissingCredentials("AWS_ACCESS_KEY_ID"))?;
+        let secret_key = env::var("AWS_SECRET_ACCESS_KEY")
+            .map_err(|_| S3Error::MissingCredentials("AWS_SECRET_ACCESS_KEY"))?;
+
+        Ok(Self::new(endpoint, region, access_key, secret_key))
+    }
+
+    /// Host and port of the endpoint, as signed in requests.
+    fn host(&self) -> Result<&str> {
+        match self.endpoint.find("://") {
+            Some(idx) => Ok(&self.endpoint[idx + 3..]),
+            None => Err(S3Error::InvalidEndpoint(self.endpoint.clone()).into()),
+        }
+    }
+}
+
+/// Request in the canonical form signed with AWS Signature Version 4: its URI and query
+/// already encoded, the query sorted, and every signed header.
+pub struct CanonicalRequest<'a> {
+    method: &'a str,
+    uri: &'a str,
+    query: &'a str,
+    headers: Vec<(String, String)>,
+    payload_hash: &'a str,
+}
+
+/// Signature of a request, along with the string signed to compute it.
+pub struct Signature {
+    pub string_to_sign: String,
+    pub authorization: String,
+}
+
+impl<'a> CanonicalRequest<'a> {
+    pub fn new(
+        method: &'a str,
+        uri: &'a str,
+        query: &'a str,
+        headers: Vec<(String, String)>,
+        payload_hash: &'a str,
+    ) -> Self {
+        let mut headers = headers
+            .into_iter()
+            .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+            .collect::<Vec<(String, String)>>();
+        headers.sort();
+
+        Self {
+            method,
+            uri,
+            query,
+            headers,
+            payload_hash,
+        }
+    }
+
+    fn header_names(&self) -> String {
+        self.headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>()
+            .join(";")
+    }
+
+    /// Signs the request sent at `date_time`, formatted as `%Y%m%dT%H%M%SZ`, to `service` with
+    /// the region and credentials of `config`.
+    pub fn sign(&self, config: &S3Config, service: &str, date_time: &str) -> Signature {
+        let date = &date_time[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, config.region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            date_time,
+            scope,
+            hex::encode(Sha256::digest(self.to_string().as_bytes()))
+        );
+
+        let mut signing_key = format!("AWS4{}", config.secret_key).into_bytes();
+        for part in &[date, &config.region, service, "aws4_request"] {
+            signing_key = hmac(&signing_key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac(&signing_key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            config.access_key,
+            scope,
+            self.header_names(),
+            signature
+        );
+
+        Signature {
+            string_to_sign,
+            authorization,
+        }
+    }
+}
+
+impl fmt::Display for CanonicalRequest<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}\n{}\n{}", self.method, self.uri, self.query)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{}:{}", name, value)?;
+        }
+        write!(f, "\n{}\n{}", self.header_names(), self.payload_hash)
+    }
+}
+
+/// Requests to the S3 API signed with AWS Signature Version 4.
+struct S3Client {
+    config: S3Config,
+    agent: ureq::Agent,
+}
+
+impl S3Client {
+    const SERVICE: &'static str = "s3";
+
+    fn new(config: S3Config) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(10))
+            .timeout_read(Duration::from_secs(60))
+            .build();
+        Self { config, agent }
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        bucket: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, String)],
+        body: &[u8],
+    ) -> Result<ureq::Response> {
+        let mut description = format!("{} {}", method, bucket);
+        let mut uri = format!("/{}", uri_encode(bucket, true));
+        if !key.is_empty() {
+            description.push_str(&format!("/{}", key));
+            uri.push('/');
+            uri.push_str(&uri_encode(key, false));
+        }
+
+        let mut query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", uri_encode(name, true), uri_encode(value, true)))
+            .collect::<Vec<String>>();
+        query.sort();
+        let query = query.join("&");
+
+        let now = Utc::now();
+        let date_time = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(Sha256::digest(body));
+
+        let mut signed_headers = vec![
+            ("host".to_string(), self.config.host()?.to_string()),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), date_time.clone()),
+        ];
+        for (name, value) in headers {
+            signed_headers.push((name.to_string(), value.clone()));
+        }
+
+        let canonical_request =
+            CanonicalRequest::new(method, &uri, &query, signed_headers, &payload_hash);
+        let signature = canonical_request.sign(&self.config, Self::SERVICE, &date_time);
+
+        let url = match query.is_empty() {
+            true => format!("{}{}", self.config.endpoint, uri),
+            false => format!("{}{}?{}", self.config.endpoint, uri, query),
+        };
+        let mut request = self
+            .agent
+            .request(method, &url)
+            .set("Authorization", &signature.authorization);
+        for (name, value) in &canonical_request.headers {
+            if name != "host" {
+                request = request.set(name, value);
+            }
+        }
+
+        match request.send_bytes(body) {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(status, response)) => {
+                let message = match response.into_string() {
+                    Ok(body) => error_message(&body).unwrap_or(body),
+                    Err(error) => error.to_string(),
+                };
+                Err(S3Error::Status(description, status, message).into())
+            }
+            Err(error) => {
+                let kind = error.kind();
+                Err(S3Error::Transport(description, error.to_string(), kind).into())
+            }
+        }
+    }
+
+    fn head_object(&self, bucket: &str, key: &str) -> Result<u64> {
+        let response = self.request("HEAD", bucket, key, &[], &[], &[])?;
+
+        response
+            .header("Content-Length")
+            .and_then(|length| length.parse().ok())
+            .ok_or_else(|| {
+                let description = format!("HEAD {}/{}", bucket, key);
+                S3Error::InvalidResponse(description, "missing Content-Length".to_string()).into()
+            })
+    }
+
+    /// Reads an object, or `length` of its bytes from `start`.
+    fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        range: Option<(u64, u64)>,
+    ) -> Result<Box<dyn Read + Send + Sync>> {
+        let headers = match range {
+            Some((start, length)) => {
+                vec![("Range", format!("bytes={}-{}", start, start + length - 1))]
+            }
+            None => vec![],
+        };
+
+        let response = self.request("GET", bucket, key, &[], &headers, &[])?;
+        Ok(response.into_reader())
+    }
+
+    fn put_object(&self, bucket: &str, key: &str, body: &[u8]) -> Result<()> {
+        self.request("PUT", bucket, key, &[], &[], body)?;
+        Ok(())
+    }
+
+    fn copy_object(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<()> {
+        let source = format!(
+            "/{}/{}",
+            uri_encode(source_bucket, true),
+            uri_encode(source_key, false)
+        );
+        self.request(
+            "PUT",
+            bucket,
+            key,
+            &[],
+            &[("x-amz-copy-source", source)],
+            &[],
+        )?;
+        Ok(())
+    }
+
+    fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        self.request("DELETE", bucket, key, &[], &[], &[])?;
+        Ok(())
+    }
+
+    /// Keys and sizes of the objects starting with `prefix`, only those directly under it
+    /// with `delimited`.
+    fn list_objects(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        delimited: bool,
+    ) -> Result<Vec<(String, u64)>> {
+        let description = format!("GET {}?prefix={}", bucket, prefix);
+        let mut objects = vec![];
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if delimited {
+                query.push(("delimiter", "/"));
+            }
+            if let Some(token) = &continuation_token {
+                query.push(("continuation-token", token));
+            }
+
+            let body = self
+                .request("GET", bucket, "", &query, &[], &[])?
+                .into_string()?;
+            let page = ListPage::parse(&description, &body)?;
+
+            objects.extend(page.objects);
+            continuation_token = page.continuation_token;
+            if continuation_token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+
+    fn create_multipart_upload(&self, bucket: &str, key: &str) -> Result<String> {
+        let body = self
+            .request("POST", bucket, key, &[("uploads", "")], &[], &[])?
+            .into_string()?;
+        let document = roxmltree::Document::parse(&body)?;
+
+        child_text(&document.root_element(), "UploadId").ok_or_else(|| {
+            let description = format!("POST {}/{}?uploads", bucket, key);
+            S3Error::InvalidResponse(description, "missing UploadId".to_string()).into()
+        })
+    }
+
+    /// Uploads a part of a multipart upload, returning its ETag.
+    fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: usize,
+        body: &[u8],
+    ) -> Result<String> {
+        let part_number = part_number.to_string();
+        let query = [
+            ("partNumber", part_number.as_str()),
+            ("uploadId", upload_id),
+        ];
+        let response = self.request("PUT", bucket, key, &query, &[], body)?;
+
+        response
+            .header("ETag")
+            .map(|etag| etag.to_string())
+            .ok_or_else(|| {
+                let description = format!("PUT {}/{}?partNumber={}", bucket, key, part_number);
+                S3Error::InvalidResponse(description, "missing ETag".to_string()).into()
+            })
+    }
+
+    fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        etags: &[String],
+    ) -> Result<()> {
+        let parts = etags
+            .iter()
+            .enumerate()
+            .map(|(idx, etag)| {
+                format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                    idx + 1,
+                    etag
+                )
+            })
+            .collect::<String>();
+        let body = format!(
+            "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+            parts
+        );
+
+        let query = [("uploadId", upload_id)];
+        self.request("POST", bucket, key, &query, &[], body.as_bytes())?;
+        Ok(())
+    }
+
+    fn abort_multipart_upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<()> {
+        self.request("DELETE", bucket, key, &[("uploadId", upload_id)], &[], &[])?;
+        Ok(())
+    }
+}
+
+/// Page of a ListObjectsV2 response: keys and sizes of objects, along with the token of the
+/// next page if the listing is truncated.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ListPage {
+    pub objects: Vec<(String, u64)>,
+    pub continuation_token: Option<String>,
+}
+
+impl ListPage {
+    /// Parses the response to the request of `description`, which errors mention.
+    pub fn parse(description: &str, body: &str) -> Result<Self> {
+        let invalid = |message: String| S3Error::InvalidResponse(description.to_string(), message);
+        let document =
+            roxmltree::Document::parse(body).map_err(|error| invalid(error.to_string()))?;
+
+        let mut objects = vec![];
+        for contents in document
+            .descendants()
+            .filter(|node| node.has_tag_name("Contents"))
+        {
+            let key = child_text(&contents, "Key");
+            let size = child_text(&contents, "Size").and_then(|size| size.parse().ok());
+            match (key, size) {
+                (Some(key), Some(size)) => objects.push((key, size)),
+                _ => return Err(invalid("object without key or size".to_string()).into()),
+            }
+        }
+
+        let root = document.root_element();
+        let continuation_token = match child_text(&root, "IsTruncated").as_deref() {
+            Some("true") => child_text(&root, "NextContinuationToken"),
+            _ => None,
+        };
+
+        Ok(Self {
+            objects,
+            continuation_token,
+        })
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes everything but unreserved characters, and slashes unless `encode_slash`.
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+fn child_text(node: &roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(|text| text.to_string())
+}
+
+/// Code and message of an S3 error response.
+fn error_message(body: &str) -> Option<String> {
+    let document = roxmltree::Document::parse(body).ok()?;
+    let root = document.root_element();
+
+    match (child_text(&root, "Code"), child_text(&root, "Message")) {
+        (Some(code), Some(message)) => Some(format!("{}: {}", code, message)),
+        (Some(code), None) => Some(code),
+        _ => None,
+    }
+}
+
+/// Reads ranges of an object, e.g. the footer of a Parquet object without fetching its rows.
+/// Parquet errors only carry messages, so the error of a failed request is kept aside for the
+/// caller to return instead.
+struct S3ObjectReader<'a> {
+    client: &'a S3Client,
+    bucket: &'a str,
+    key: String,
+    size: u64,
+    error: RefCell<Option<anyhow::Error>>,
+}
+
+impl Length for S3ObjectReader<'_> {
+    fn len(&self) -> u64 {
+        self.size
+    }
+}
+
+impl ChunkReader for S3ObjectReader<'_> {
+    type T = io::Cursor<Vec<u8>>;
+
+    fn get_read(&self, start: u64, length: usize) -> ParquetResult<Self::T> {
+        let mut bytes = Vec::with_capacity(length);
+        let result = self
+            .client
+            .get_object(self.bucket, &self.key, Some((start, length as u64)))
+            .and_then(|mut reader| Ok(reader.read_to_end(&mut bytes)?));
+        if let Err(error) = result {
+            let message = format!("{:#}", error);
+            self.error.replace(Some(error));
+            return Err(ParquetError::General(message));
+        }
+        Ok(io::Cursor::new(bytes))
+    }
+}
+
+/// Store of S3-compatible object storage, where partitions are key prefixes of datasets.
+pub struct S3Store {
+    client: S3Client,
+    scratch_count: AtomicUsize,
+}
+
+impl S3Store {
+    /// Outputs larger than a part are uploaded in parts, S3 requires at least 5 MiB per part.
+    const PART_SIZE: usize = 8 * 1024 * 1024;
+
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            client: S3Client::new(config),
+            scratch_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Key of a dataset, partition or object in its bucket.
+    fn key(bucket: &str, path: &Path) -> String {
+        let path = path.strip_prefix(bucket).unwrap_or(path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn object_key(path: &ObjectPath) -> String {
+        Self::key(&Self::bucket(path.partition_path()), &path.std_path())
+    }
+
+    fn dataset_prefix(path: &DatasetPath) -> String {
+        format!("{}/", Self::key(&path.bucket().name, &path.std_path()))
+    }
+
+    fn partition_prefix(path: &PartitionPath) -> String {
+        format!("{}/", Self::key(&Self::bucket(path), &path.std_path()))
+    }
+
+    fn bucket(path: &PartitionPath) -> String {
+        path.dataset.bucket().name.clone()
+    }
+
+    /// Fails unless no object starts with `prefix`, as the file store does with directories.
+    fn check_empty(&self, bucket: &str, prefix: &str) -> Result<()> {
+        match self.client.list_objects(bucket, prefix, false)?.is_empty() {
+            true => Ok(()),
+            false => Err(S3Error::NotEmpty(format!("{}/{}", bucket, prefix)).into()),
+        }
+    }
+
+    fn scratch(&self) -> Result<Scratch> {
+        Scratch::new("s3", self.scratch_count.fetch_add(1, Ordering::SeqCst))
+    }
+
+    fn download(&self, scratch: &Scratch, paths: &[ObjectPath]) -> Result<()> {
+        for path in paths {
+            let fs_path = scratch.fs_path(path);
+            if let Some(parent) = fs_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut reader = self.client.get_object(
+                &Self::bucket(path.partition_path()),
+                &Self::object_key(path),
+                None,
+            )?;
+            let mut file = fs::File::create(&fs_path)?;
+            io::copy(&mut reader, &mut file)
+                .with_context(|| format!("cannot download object: {}", path))?;
+        }
+
+        Ok(())
+    }
+
+    fn upload(&self, scratch: &Scratch, paths: &[ObjectPath]) -> Result<()> {
+        for path in paths {
+            let mut file = fs::File::open(scratch.fs_path(path))
+                .with_context(|| format!("object to upload not found: {}", path))?;
+            self.upload_object(path, &mut file)
+                .with_context(|| format!("cannot upload object: {}", path))?;
+        }
+
+        Ok(())
+    }
+
+    /// Uploads an object in one request, or in parts if it is larger than a part.
+    fn upload_object(&self, path: &ObjectPath, reader: &mut dyn Read) -> Result<()> {
+        let bucket = Self::bucket(path.partition_path());
+        let key = Self::object_key(path);
+
+        let first_part = Self::read_part(reader)?;
+        if first_part.len() < Self::PART_SIZE {
+            return self.client.put_object(&bucket, &key, &first_part);
+        }
+
+        let upload_id = self.client.create_multipart_upload(&bucket, &key)?;
+        let upload_parts = || {
+            let mut etags = vec![];
+            let mut part = first_part;
+
+            while !part.is_empty() {
+                let part_number = etags.len() + 1;
+                etags.push(self.client.upload_part(
+                    &bucket,
+                    &key,
+                    &upload_id,
+                    part_number,
+                    &part,
+                )?);
+                part = Self::read_part(reader)?;
+            }
+
+            self.client
+                .complete_multipart_upload(&bucket, &key, &upload_id, &etags)
+        };
+
+        let result = upload_parts();
+        if result.is_err() {
+            // Parts of an upload that is neither completed nor aborted are kept and billed
+            let _ = self
+                .client
+                .abort_multipart_upload(&bucket, &key, &upload_id);
+        }
+        result
+    }
+
+    fn read_part(reader: &mut dyn Read) -> Result<Vec<u8>> {
+        let mut part = Vec::with_capacity(Self::PART_SIZE);
+        reader.take(Self::PART_SIZE as u64).read_to_end(&mut part)?;
+        Ok(part)
+    }
+}
+
+impl Store for S3Store {
+    fn read_object(&self, path: &ObjectPath) -> Result<ObjectState> {
+        let bucket = Self::bucket(path.partition_path());
+        let key = Self::object_key(path);
+        let size = self.client.head_object(&bucket, &key)?;
+
+        match path.infer_format() {
+            Some(Format::Csv) => {
+                let mut head = vec![];
+                if size > 0 {
+                    let range = (0, size.min(Csv::HEAD_SIZE));
+                    self.client
+                        .get_object(&bucket, &key, Some(range))?
+                        .read_to_end(&mut head)?;
+                }
+                let size = Bytes::new(size as usize);
+                Csv::read_head_object_state(head, size, path.key.is_gzipped())
+            }
+            Some(Format::Parquet) => {
+                let reader = S3ObjectReader {
+                    client: &self.client,
+                    bucket: &bucket,
+                    key,
+                    size,
+                    error: RefCell::new(None),
+                };
+                let state = Parquet::read_object_state(&reader);
+                match reader.error.into_inner() {
+                    Some(error) => Err(error),
+                    None => state,
+                }
+            }
+            None => Err(StoreError::CannotInferSchema(path.clone()).into()),
+        }
+    }
+
+    fn open_object(&self, path: &ObjectPath) -> Result<Box<dyn Read + Send>> {
+        let reader = self
+            .client
+            .get_object(
+                &Self::bucket(path.partition_path()),
+                &Self::object_key(path),
+                None,
+            )
+            .with_context(|| format!("cannot open object: {}", path))?;
+        Ok(reader)
+    }
+
+    fn write_object(&self, path: &ObjectPath, reader: &mut dyn Read) -> Result<()> {
+        self.upload_object(path, reader)
+            .with_context(|| format!("cannot upload object: {}", path))
+    }
+
+    fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        self.client
+            .copy_object(
+                &Self::bucket(source.partition_path()),
+                &Self::object_key(source),
+                &Self::bucket(target.partition_path()),
+                &Self::object_key(target),
+            )
+            .with_context(|| format!("cannot copy {} to {}", source, target))
+    }
+
+    /// S3 has no moves, they are split into copies and removals by the runtime.
+    fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        self.copy_object(source, target)?;
+        self.remove_object(source)
+    }
+
+    fn supports_move(&self) -> bool {
+        false
+    }
+
+    fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<Partition>> {
+        let prefix = Self::dataset_prefix(path);
+        let objects = self
+            .client
+            .list_objects(&path.bucket().name, &prefix, false)?;
+
+        if objects.is_empty() {
+            return Err(StoreError::MissingDataset(path.clone()).into());
+        }
+
+        let mut dirs = BTreeSet::new();
+        for (key, _) in objects {
+            match key[prefix.len()..].rfind('/') {
+                Some(idx) => dirs.insert(key[prefix.len()..prefix.len() + idx].to_string()),
+                None => {
+                    return Err(
+                        StoreError::InvalidPartition(key[prefix.len()..].to_string()).into(),
+                    )
+                }
+            };
+        }
+
+        // As with directories, a prefix holding sub-partitions is not a partition itself
+        dirs.iter()
+            .filter(|dir| {
+                let children = format!("{}/", dir);
+                !dirs.iter().any(|other| other.starts_with(&children))
+            })
+            .map(|dir| match dir.parse() {
+                Ok(partition) => Ok(partition),
+                Err(_) => Err(StoreError::InvalidPartition(dir.clone()).into()),
+            })
+            .collect()
+    }
+
+    fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectKey>> {
+        let prefix = Self::partition_prefix(path);
+        let objects = self
+            .client
+            .list_objects(&Self::bucket(path), &prefix, true)?;
+
+        Ok(objects
+            .into_iter()
+            .map(|(key, _)| ObjectKey::new(key[prefix.len()..].to_string()))
+            .collect())
+    }
+
+    fn remove_dataset(&self, path: &DatasetPath) -> Result<()> {
+        self.check_empty(&path.bucket().name, &Self::dataset_prefix(path))
+    }
+
+    fn remove_partition(&self, path: &PartitionPath) -> Result<()> {
+        self.check_empty(&Self::bucket(path), &Self::partition_prefix(path))
+    }
+
+    fn remove_object(&self, path: &ObjectPath) -> Result<()> {
+        self.client
+            .delete_object(
+                &Self::bucket(path.partition_path()),
+                &Self::object_key(path),
+            )
+            .with_context(|| format!("cannot remove object: {}", path))
+    }
+
+    fn rebalance_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        output_paths: &[ObjectPath],
+        target: &RebalanceTarget,
+    ) -> Result<Vec<ObjectState>> {
+        let scratch = self.scratch()?;
+        self.download(&scratch, input_paths)?;
+        let states = scratch
+            .store
+            .rebalance_objects(input_paths, output_paths, target)?;
+        self.upload(&scratch, output_paths)?;
+        Ok(states)
+    }
+
+    fn sample_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        sampler: &Sampler,
+    ) -> Result<ObjectState> {
+        let scratch = self.scratch()?;
+        self.download(&scratch, std::slice::from_ref(source))?;
+        let state = scratch.store.sample_object(source, target, sampler)?;
+        self.upload(&scratch, std::slice::from_ref(target))?;
+        Ok(state)
+    }
+
+    fn repartition_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        target: &DatasetPath,
+        partitioner: &Partitioner,
+    ) -> Result<Vec<(ObjectPath, ObjectState)>> {
+        let scratch = self.scratch()?;
+        self.download(&scratch, input_paths)?;
+        let outputs = scratch
+            .store
+            .repartition_objects(input_paths, target, partitioner)?;
+
+        let output_paths = outputs
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        self.upload(&scratch, &output_paths)?;
+        Ok(outputs)
+    }
+
+    fn compress_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        compression: &Compression,
+    ) -> Result<ObjectState> {
+        let scratch = self.scratch()?;
+        self.download(&scratch, std::slice::from_ref(source))?;
+        let state = scratch.store.compress_object(source, target, compression)?;
+        self.upload(&scratch, std::slice::from_ref(target))?;
+        Ok(state)
+    }
+
+    fn generate_object(
+        &self,
+        path: &ObjectPath,
+        generator: &Generator,
+        rows: usize,
+    ) -> Result<ObjectState> {
+        let scratch = self.scratch()?;
+        let state = scratch.store.generate_object(path, generator, rows)?;
+        self.upload(&scratch, std::slice::from_ref(path))?;
+        Ok(state)
+    }
+
+    fn convert_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<ObjectState> {
+        let scratch = self.scratch()?;
+        self.download(&scratch, std::slice::from_ref(source))?;
+        let state = scratch.store.convert_object(source, target)?;
+        self.upload(&scratch, std::slice::from_ref(target))?;
+        Ok(state)
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use arrow::array::BooleanArray;
+use arrow::compute::filter_record_batch;
+use arrow::record_batch::RecordBatch;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::base::{stable_hash, Percent};
+use crate::path::ObjectPath;
+
+/// Keeps a seeded random percentage of the rows of objects.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    percent: Percent,
+    seed: u64,
+}
+
+impl Sampler {
+    pub fn new(percent: Percent, seed: u64) -> Self {
+        Self { percent, seed }
+    }
+
+    pub fn percent(&self) -> Percent {
+        self.percent
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Filter keeping the sampled rows of the batches of an object, in order. Every object
+    /// gets its own generator, seeded from its partition and key, so that the rows kept do
+    /// not depend on the order objects are sampled in nor on the bucket they are in.
+    pub fn object_filter(
+        &self,
+        path: &ObjectPath,
+    ) -> impl FnMut(&RecordBatch) -> Result<RecordBatch> {
+        let location = format!("{}/{}", path.get_partition(), path.key);
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed ^ stable_hash(&location));
+        let fraction = self.percent.as_fraction();
+
+        move |batch| {
+            let mask = (0..batch.num_rows())
+                .map(|_| Some(rng.gen_bool(fraction)))
+                .collect::<BooleanArray>();
+            Ok(filter_record_batch(batch, &mask)?)
+        }
+    }
+}
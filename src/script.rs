@@ -0,0 +1,441 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use anyhow::Result;
+
+use crate::base::{Bytes, Format, ParseError, Partition, Percent};
+use crate::compress::Compression;
+use crate::generate::Generator;
+use crate::job::{
+    Compress, ConvertFormat, CopyDataset, CopyPartition, Generate, Job, MoveDataset, MovePartition,
+    RebalanceObjects, ReloadDataset, RemoveDataset, Repartition, Sample, VerifyDataset,
+};
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::state::State;
+use crate::view::{CompressionReport, ListObjects, ListPartitions, SizeOf, View};
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("line {0}: invalid statement: {1}")]
+    InvalidStatement(usize, String),
+
+    #[error("line {0}: unknown operation: {1}")]
+    UnknownOperation(usize, String),
+
+    #[error("line {0}: {1} expects arguments ({2})")]
+    InvalidArguments(usize, String, &'static str),
+
+    #[error("line {0}: {1}")]
+    InvalidArgument(usize, ParseError),
+}
+
+fn parse_arg<T: FromStr<Err = ParseError>>(line: usize, arg: &str) -> Result<T, ScriptError> {
+    arg.parse()
+        .map_err(|error| ScriptError::InvalidArgument(line, error))
+}
+
+fn parse_number(line: usize, arg: &str) -> Result<u64, ScriptError> {
+    arg.parse()
+        .map_err(|_| ScriptError::InvalidArgument(line, ParseError::InvalidNumber(arg.to_string())))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    Reload(DatasetPath),
+    Verify(DatasetPath),
+    MovePartition(PartitionPath, PartitionPath),
+    CopyPartition(PartitionPath, PartitionPath),
+    CopyDataset(DatasetPath, DatasetPath),
+    MoveDataset(DatasetPath, DatasetPath),
+    RemoveDataset(DatasetPath),
+    Rebalance(PartitionPath, Bytes),
+    Sample(DatasetPath, DatasetPath, Vec<Partition>, Percent, u64),
+    Repartition(DatasetPath, DatasetPath, Vec<String>),
+    Compress(DatasetPath, DatasetPath, Vec<Partition>, Compression),
+    Generate(ObjectPath, Generator, usize),
+    Convert(DatasetPath, DatasetPath, Format),
+    Ls(DatasetPath, bool),
+    LsObjects(PartitionPath),
+    SizeOf(DatasetPath, Vec<Partition>, bool),
+}
+
+impl Operation {
+    pub const DEFAULT_REBALANCE_SIZE: &'static str = "15MiB";
+
+    fn parse(line: usize, name: &str, args: &[&str]) -> Result<Self, ScriptError> {
+        let invalid = |usage| Err(ScriptError::InvalidArguments(line, name.to_string(), usage));
+
+        match (name, args.len()) {
+            ("reload", 1) => Ok(Operation::Reload(parse_arg(line, args[0])?)),
+            ("reload", _) => invalid("dataset"),
+            ("verify", 1) => Ok(Operation::Verify(parse_arg(line, args[0])?)),
+            ("verify", _) => invalid("dataset"),
+            ("move-partition", 2) => Ok(Operation::MovePartition(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+            )),
+            ("move-partition", _) => invalid("source, target"),
+            ("copy-partition", 2) => Ok(Operation::CopyPartition(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+            )),
+            ("copy-partition", _) => invalid("source, target"),
+            ("copy-dataset", 2) => Ok(Operation::CopyDataset(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+            )),
+            ("copy-dataset", _) => invalid("source, target"),
+            ("move-dataset", 2) => Ok(Operation::MoveDataset(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+            )),
+            ("move-dataset", _) => invalid("source, target"),
+            ("remove-dataset", 1) => Ok(Operation::RemoveDataset(parse_arg(line, args[0])?)),
+            ("remove-dataset", _) => invalid("dataset"),
+            ("rebalance", 1) => Ok(Operation::Rebalance(
+                parse_arg(line, args[0])?,
+                Self::DEFAULT_REBALANCE_SIZE.parse().unwrap(),
+            )),
+            ("rebalance", 2) => Ok(Operation::Rebalance(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+            )),
+            ("rebalance", _) => invalid("partition, [size]"),
+            ("sample", 3..=usize::MAX) => {
+                // The seed is told apart from partitions by being a number
+                let seed = args.get(3).and_then(|arg| arg.parse().ok());
+                let partitions = match seed {
+                    Some(_) => &args[4..],
+                    None => &args[3..],
+                };
+
+                Ok(Operation::Sample(
+                    parse_arg(line, args[0])?,
+                    parse_arg(line, args[1])?,
+                    partitions
+                        .iter()
+                        .map(|arg| parse_arg(line, arg))
+                        .collect::<Result<Vec<Partition>, ScriptError>>()?,
+                    parse_arg(line, args[2])?,
+                    seed.unwrap_or(0),
+                ))
+            }
+            ("sample", _) => invalid("source, target, percent, [seed], [partition...]"),
+            ("repartition", 3..=usize::MAX) => Ok(Operation::Repartition(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+                args[2..].iter().map(|arg| arg.to_string()).collect(),
+            )),
+            ("repartition", _) => invalid("source, target, column..."),
+            ("compress", 3..=usize::MAX) => {
+                // The gzip level of CSV objects is told apart from partitions by being a number
+                let csv_level = args.get(3).and_then(|arg| arg.parse().ok());
+                let partitions = match csv_level {
+                    Some(_) => &args[4..],
+                    None => &args[3..],
+                };
+
+                Ok(Operation::Compress(
+                    parse_arg(line, args[0])?,
+                    parse_arg(line, args[1])?,
+                    partitions
+                        .iter()
+                        .map(|arg| parse_arg(line, arg))
+                        .collect::<Result<Vec<Partition>, ScriptError>>()?,
+                    Compression::new(parse_arg(line, args[2])?, csv_level),
+                ))
+            }
+            ("generate", 3..=5) => Ok(Operation::Generate(
+                parse_arg(line, args[0])?,
+                Generator::new(
+                    parse_arg(line, args[1])?,
+                    args.get(3)
+                        .map_or(Ok(Percent::new(0).unwrap()), |arg| parse_arg(line, arg))?,
+                    args.get(4).map_or(Ok(0), |arg| parse_number(line, arg))?,
+                ),
+                parse_number(line, args[2])? as usize,
+            )),
+            ("generate", _) => invalid("object, columns, rows, [null percent], [seed]"),
+            ("compress", _) => invalid("source, target, codec, [csv gzip level], [partition...]"),
+            ("convert", 3) => Ok(Operation::Convert(
+                parse_arg(line, args[0])?,
+                parse_arg(line, args[1])?,
+                parse_arg(line, args[2])?,
+            )),
+            ("convert", _) => invalid("source, target, format: csv|parquet"),
+            ("ls", 1) => Ok(Operation::Ls(parse_arg(line, args[0])?, false)),
+            ("ls", 2) => match args[1].parse() {
+                Ok(objects) => Ok(Operation::Ls(parse_arg(line, args[0])?, objects)),
+                Err(_) => invalid("dataset, [objects: true|false]"),
+            },
+            ("ls", _) => invalid("dataset, [objects: true|false]"),
+            ("ls-objects", 1) => Ok(Operation::LsObjects(parse_arg(line, args[0])?)),
+            ("ls-objects", _) => invalid("partition"),
+            ("size-of", 1..=usize::MAX) => {
+                // Whether the sizes are detailed is told apart from partitions by being a bool
+                let detailed = args.get(1).and_then(|arg| arg.parse().ok());
+                let partitions = match detailed {
+                    Some(_) => &args[2..],
+                    None => &args[1..],
+                };
+
+                Ok(Operation::SizeOf(
+                    parse_arg(line, args[0])?,
+                    partitions
+                        .iter()
+                        .map(|arg| parse_arg(line, arg))
+                        .collect::<Result<Vec<Partition>, ScriptError>>()?,
+                    detailed.unwrap_or(false),
+                ))
+            }
+            ("size-of", _) => invalid("dataset, [detailed: true|false], [partition...]"),
+            _ => Err(ScriptError::UnknownOperation(line, name.to_string())),
+        }
+    }
+
+    pub fn job(&self) -> Option<Box<dyn Job>> {
+        match self {
+            Operation::Reload(path) => Some(Box::new(ReloadDataset::new(path.clone()))),
+            Operation::Verify(path) => Some(Box::new(VerifyDataset::new(path.clone()))),
+            Operation::MovePartition(source, target) => {
+                Some(Box::new(MovePartition::new(source.clone(), target.clone())))
+            }
+            Operation::CopyPartition(source, target) => {
+                Some(Box::new(CopyPartition::new(source.clone(), target.clone())))
+            }
+            Operation::CopyDataset(source, target) => {
+                Some(Box::new(CopyDataset::new(source.clone(), target.clone())))
+            }
+            Operation::MoveDataset(source, target) => {
+                Some(Box::new(MoveDataset::new(source.clone(), target.clone())))
+            }
+            Operation::RemoveDataset(path) => Some(Box::new(RemoveDataset::new(path.clone()))),
+            Operation::Rebalance(path, size) => {
+                Some(Box::new(RebalanceObjects::new(path.clone(), *size)))
+            }
+            Operation::Sample(source, target, partitions, percent, seed) => {
+                Some(Box::new(Sample::new(
+                    source.clone(),
+                    target.clone(),
+                    partitions.clone(),
+                    *percent,
+                    *seed,
+                )))
+            }
+            Operation::Repartition(source, target, keys) => Some(Box::new(Repartition::new(
+                source.clone(),
+                target.clone(),
+                keys.clone(),
+            ))),
+            Operation::Compress(source, target, partitions, compression) => {
+                Some(Box::new(Compress::new(
+                    source.clone(),
+                    target.clone(),
+                    partitions.clone(),
+                    *compression,
+                )))
+            }
+            Operation::Generate(path, generator, rows) => Some(Box::new(Generate::new(
+                path.clone(),
+                generator.clone(),
+                *rows,
+            ))),
+            Operation::Convert(source, target, format) => Some(Box::new(ConvertFormat::new(
+                source.clone(),
+                target.clone(),
+                format.clone(),
+            ))),
+            Operation::Ls(_, _) | Operation::LsObjects(_) | Operation::SizeOf(_, _, _) => None,
+        }
+    }
+
+    /// View of the result of the operation, built from the state before the operation runs.
+    /// Every operation has one, so that dry runs show what they predict.
+    pub fn view(&self, state: &State) -> Result<Box<dyn View>> {
+        Ok(match self {
+            Operation::Reload(path) | Operation::Verify(path) => {
+                Box::new(ListPartitions::new(path.clone(), true))
+            }
+            Operation::RemoveDataset(path) => Box::new(ListPartitions::new(path.clone(), false)),
+            Operation::MovePartition(_, target) | Operation::CopyPartition(_, target) => {
+                Box::new(ListPartitions::new(target.dataset.clone(), true))
+            }
+            Operation::CopyDataset(_, target)
+            | Operation::MoveDataset(_, target)
+            | Operation::Sample(_, target, _, _, _)
+            | Operation::Repartition(_, target, _)
+            | Operation::Convert(_, target, _) => {
+                Box::new(ListPartitions::new(target.clone(), true))
+            }
+            Operation::Rebalance(path, _) => Box::new(ListObjects::new(path.clone())),
+            Operation::Ls(path, objects) => Box::new(ListPartitions::new(path.clone(), *objects)),
+            Operation::LsObjects(path) => Box::new(ListObjects::new(path.clone())),
+            Operation::SizeOf(path, partitions, detailed) => {
+                Box::new(SizeOf::new(path.clone(), partitions.clone(), *detailed))
+            }
+            Operation::Generate(path, _, _) => {
+                Box::new(ListObjects::new(path.partition_path().clone()))
+            }
+            Operation::Compress(source, target, partitions, compression) => {
+                let job = Compress::new(
+                    source.clone(),
+                    target.clone(),
+                    partitions.clone(),
+                    *compression,
+                );
+
+                let mut objects = vec![];
+                for (source, target) in job.outputs(state)? {
+                    let size = state.get_object(&source)?.size;
+                    objects.push((source, target, size));
+                }
+                Box::new(CompressionReport::new(objects))
+            }
+        })
+    }
+
+    pub fn dependencies(&self) -> Vec<DatasetPath> {
+        match self {
+            Operation::Ls(path, _) => vec![path.clone()],
+            Operation::LsObjects(path) => vec![path.dataset.clone()],
+            Operation::SizeOf(path, _, _) => vec![path.clone()],
+            _ => self.job().map_or_else(Vec::new, |job| job.dependencies()),
+        }
+    }
+
+    pub fn targets(&self) -> Vec<DatasetPath> {
+        self.job().map_or_else(Vec::new, |job| job.targets())
+    }
+}
+
+/// Written as a statement that parses back into the same operation.
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::Reload(path) => write!(f, "reload({})", path),
+            Operation::Verify(path) => write!(f, "verify({})", path),
+            Operation::MovePartition(source, target) => {
+                write!(f, "move-partition({}, {})", source, target)
+            }
+            Operation::CopyPartition(source, target) => {
+                write!(f, "copy-partition({}, {})", source, target)
+            }
+            Operation::CopyDataset(source, target) => {
+                write!(f, "copy-dataset({}, {})", source, target)
+            }
+            Operation::MoveDataset(source, target) => {
+                write!(f, "move-dataset({}, {})", source, target)
+            }
+            Operation::RemoveDataset(path) => write!(f, "remove-dataset({})", path),
+            Operation::Rebalance(path, size) => {
+                write!(f, "rebalance({}, {}B)", path, size.as_usize())
+            }
+            Operation::Sample(source, target, partitions, percent, seed) => {
+                write!(f, "sample({}, {}, {}, {}", source, target, percent, seed)?;
+                for partition in partitions {
+                    write!(f, ", {}", partition)?;
+                }
+                write!(f, ")")
+            }
+            Operation::Repartition(source, target, keys) => {
+                write!(
+                    f,
+                    "repartition({}, {}, {})",
+                    source,
+                    target,
+                    keys.join(", ")
+                )
+            }
+            Operation::Compress(source, target, partitions, compression) => {
+                write!(f, "compress({}, {}, {}", source, target, compression.codec)?;
+                if let Some(level) = compression.csv_level {
+                    write!(f, ", {}", level)?;
+                }
+                for partition in partitions {
+                    write!(f, ", {}", partition)?;
+                }
+                write!(f, ")")
+            }
+            Operation::Generate(path, generator, rows) => write!(
+                f,
+                "generate({}, {}, {}, {}, {})",
+                path,
+                generator.columns(),
+                rows,
+                generator.null_ratio(),
+                generator.seed()
+            ),
+            Operation::Convert(source, target, format) => {
+                write!(f, "convert({}, {}, {})", source, target, format)
+            }
+            Operation::Ls(path, objects) => write!(f, "ls({}, {})", path, objects),
+            Operation::LsObjects(path) => write!(f, "ls-objects({})", path),
+            Operation::SizeOf(path, partitions, detailed) => {
+                write!(f, "size-of({}, {}", path, detailed)?;
+                for partition in partitions {
+                    write!(f, ", {}", partition)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub line: usize,
+    pub text: String,
+    pub operation: Operation,
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] {}", self.line, self.text)
+    }
+}
+
+/// A list of operations, written one or more per line as `name(arg, ...)`.
+/// Everything following a `#` on a line is ignored.
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub statements: Vec<Statement>,
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut statements = vec![];
+
+        for (idx, line) in s.lines().enumerate() {
+            let line_number = idx + 1;
+            let mut rest = line.split('#').next().unwrap_or("").trim();
+
+            while !rest.is_empty() {
+                let (open, close) = match (rest.find('('), rest.find(')')) {
+                    (Some(open), Some(close)) if open < close => (open, close),
+                    _ => return Err(ScriptError::InvalidStatement(line_number, rest.to_string())),
+                };
+
+                let name = rest[0..open].trim();
+                let args = rest[open + 1..close]
+                    .split(',')
+                    .map(|arg| arg.trim())
+                    .filter(|arg| !arg.is_empty())
+                    .collect::<Vec<&str>>();
+
+                statements.push(Statement {
+                    line: line_number,
+                    text: rest[0..=close].to_string(),
+                    operation: Operation::parse(line_number, name, &args)?,
+                });
+
+                rest = rest[close + 1..].trim_start();
+            }
+        }
+
+        Ok(Script { statements })
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use arrow::datatypes::Schema;
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::printer::print_schema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::base::{Bytes, ObjectKey, Partition};
+use crate::path::DatasetPath;
+use crate::state::{
+    CsvFormatState, DatasetState, FormatState, ObjectState, ParquetFormatState, PartitionState,
+    State,
+};
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Invalid snapshot {0}: {1}")]
+    Invalid(PathBuf, String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum FormatEntry {
+    Csv { schema: Value, delimiter: String },
+    Parquet { schema: String, num_rows: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectEntry {
+    key: String,
+    size: usize,
+    format: FormatEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PartitionEntry {
+    partition: String,
+    objects: Vec<ObjectEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DatasetEntry {
+    path: String,
+    partitions: Vec<PartitionEntry>,
+}
+
+/// The datasets of a State saved in a local file, so that they can be reused across runs
+/// instead of being reloaded from the store.
+pub struct Snapshot {
+    path: PathBuf,
+}
+
+impl Snapshot {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Marker next to the snapshot, present while the snapshot may not match the store.
+    fn stale_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".stale");
+        PathBuf::from(name)
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.stale_path().exists()
+    }
+
+    /// Marks the snapshot as not matching the store anymore, until it is saved again.
+    pub fn mark_stale(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::write(self.stale_path(), "")?;
+        }
+        Ok(())
+    }
+
+    /// Loads the saved state, or returns None if there is no snapshot or it is stale.
+    pub fn load(&self) -> Result<Option<State>> {
+        if self.is_stale() {
+            return Ok(None);
+        }
+
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        let entries: Vec<DatasetEntry> = serde_json::from_str(&contents)?;
+        let mut state = State::new();
+
+        for entry in entries {
+            let path: DatasetPath = entry.path.parse()?;
+            state = state.insert_dataset(&path, self.dataset_state(entry.partitions)?)?;
+        }
+
+        Ok(Some(state.checkpoint()))
+    }
+
+    /// Saves every dataset of the state, replacing the snapshot and clearing its stale marker.
+    pub fn save(&self, state: &State) -> Result<()> {
+        let mut entries = vec![];
+
+        for path in state.list_datasets() {
+            let mut partitions = vec![];
+
+            for partition_path in state.list_partitions(&path)? {
+                let mut objects = vec![];
+
+                for object_path in state.list_objects(&partition_path)? {
+                    let object = state.get_object(&object_path)?;
+                    objects.push(ObjectEntry {
+                        key: object_path.key.to_string(),
+                        size: object.size.as_usize(),
+                        format: Self::format_entry(&object.format),
+                    });
+                }
+
+                partitions.push(PartitionEntry {
+                    partition: partition_path.partition.to_string(),
+                    objects,
+                });
+            }
+
+            entries.push(DatasetEntry {
+                path: path.to_string(),
+                partitions,
+            });
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Written next to the snapshot first so that an interrupted save keeps the old one
+        let mut tmp_path = self.path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, serde_json::to_string(&entries)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        match fs::remove_file(self.stale_path()) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn format_entry(format: &FormatState) -> FormatEntry {
+        match format {
+            FormatState::Csv(state) => FormatEntry::Csv {
+                schema: state.schema().to_json(),
+                delimiter: state.delimiter().to_string(),
+            },
+            FormatState::Parquet(state) => {
+                let mut schema = vec![];
+                print_schema(&mut schema, state.schema());
+
+                FormatEntry::Parquet {
+                    schema: String::from_utf8_lossy(&schema).to_string(),
+                    num_rows: state.num_rows(),
+                }
+            }
+        }
+    }
+
+    fn dataset_state(&self, partitions: Vec<PartitionEntry>) -> Result<DatasetState> {
+        let mut dataset = im::HashMap::new();
+
+        for entry in partitions {
+            let partition: Partition = entry.partition.parse()?;
+            let mut objects = im::HashMap::new();
+
+            for object in entry.objects {
+                let size = Bytes::new(object.size);
+                let state = match object.format {
+                    FormatEntry::Csv { schema, delimiter } => {
+                        let schema = Schema::from(&schema)?;
+                        ObjectState::new_csv(CsvFormatState::new(schema, delimiter), size)
+                    }
+                    FormatEntry::Parquet { schema, num_rows } => {
+                        let schema = parse_message_type(&schema).map_err(|error| {
+                            SnapshotError::Invalid(self.path.clone(), error.to_string())
+                        })?;
+                        ObjectState::new_parquet(ParquetFormatState::new(schema, num_rows), size)
+                    }
+                };
+                objects.insert(ObjectKey::new(object.key), state);
+            }
+
+            dataset.insert(partition, PartitionState::new(objects));
+        }
+
+        Ok(DatasetState::new(dataset))
+    }
+}
@@ -2,13 +2,15 @@ use std::fmt;
 
 use anyhow::Result;
 use arrow::datatypes::Schema;
-use im::HashMap;
+use im::{HashMap, HashSet};
 use parquet::schema::types::Type as ParquetType;
+use serde_json::{json, Value};
 use thiserror::Error;
 
 use crate::base::{Bytes, ObjectKey, Partition};
 use crate::path::{DatasetPath, ObjectPath, PartitionPath};
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum StateError {
     #[error("Missing dataset: {0}")]
@@ -31,6 +33,14 @@ impl CsvFormatState {
     pub fn new(schema: Schema, delimiter: String) -> Self {
         CsvFormatState { schema, delimiter }
     }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn delimiter(&self) -> &str {
+        &self.delimiter
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +53,14 @@ impl ParquetFormatState {
     pub fn new(schema: ParquetType, num_rows: usize) -> Self {
         Self { schema, num_rows }
     }
+
+    pub fn schema(&self) -> &ParquetType {
+        &self.schema
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -60,6 +78,15 @@ impl FormatState {
     }
 }
 
+impl FormatState {
+    pub fn to_json(&self) -> Value {
+        match self {
+            FormatState::Csv(state) => json!({"type": "csv", "delimiter": state.delimiter}),
+            FormatState::Parquet(state) => json!({"type": "parquet", "num_rows": state.num_rows}),
+        }
+    }
+}
+
 impl fmt::Display for FormatState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -93,6 +120,10 @@ impl ObjectState {
     pub fn num_rows(&self) -> Option<usize> {
         self.format.num_rows()
     }
+
+    pub fn to_json(&self) -> Value {
+        json!({"size": self.size.as_usize(), "format": self.format.to_json()})
+    }
 }
 
 impl fmt::Display for ObjectState {
@@ -118,13 +149,6 @@ impl PartitionState {
             .ok_or_else(|| StateError::MissingObject(key.clone()))?)
     }
 
-    fn get_mut(&mut self, key: &ObjectKey) -> Result<&mut ObjectState> {
-        Ok(self
-            .objects
-            .get_mut(key)
-            .ok_or_else(|| StateError::MissingObject(key.clone()))?)
-    }
-
     pub fn size(&self) -> Bytes {
         self.objects
             .iter()
@@ -144,7 +168,7 @@ impl PartitionState {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct DatasetState {
     partitions: HashMap<Partition, PartitionState>,
 }
@@ -190,15 +214,25 @@ impl DatasetState {
     }
 }
 
+/// A location of the state modified by an operation, used to merge states.
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+enum Change {
+    Dataset(DatasetPath),
+    Partition(PartitionPath),
+    Object(ObjectPath),
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
     datasets: HashMap<DatasetPath, DatasetState>,
+    changes: HashSet<Change>,
 }
 
 impl State {
     pub fn new() -> Self {
         State {
             datasets: HashMap::new(),
+            changes: HashSet::new(),
         }
     }
 
@@ -222,11 +256,15 @@ impl State {
     }
 
     pub fn get_object(&self, path: &ObjectPath) -> Result<&ObjectState> {
-        self.get(&path.dataset_path())
-            .and_then(|ds| ds.get(&path.get_partition()))
+        self.get(path.dataset_path())
+            .and_then(|ds| ds.get(path.get_partition()))
             .and_then(|pt| pt.get(&path.key))
     }
 
+    pub fn contains_dataset(&self, path: &DatasetPath) -> bool {
+        self.datasets.contains_key(path)
+    }
+
     pub fn contains_partition(&self, path: &PartitionPath) -> bool {
         match self.get(&path.dataset) {
             Ok(dataset) => dataset.get(&path.partition).is_ok(),
@@ -246,16 +284,156 @@ impl State {
         }
     }
 
+    pub fn list_datasets(&self) -> Vec<DatasetPath> {
+        self.datasets.keys().cloned().collect()
+    }
+
+    /// Partitions of a dataset, sorted so that the actions planned from them are the same
+    /// whichever state they are planned from, e.g. when resuming a journal.
     pub fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<PartitionPath>> {
-        self.get(path)
-            .map(|ds| ds.partitions.keys().collect::<Vec<&Partition>>())
-            .map(|parts| parts.into_iter().map(|p| path.partition_path(p)).collect())
+        let mut partitions = self
+            .get(path)
+            .map(|ds| ds.partitions.keys().collect::<Vec<&Partition>>())?;
+        partitions.sort();
+        Ok(partitions.into_iter().map(|p| path.partition_path(p)).collect())
     }
 
+    /// Objects of a partition, sorted by key.
     pub fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectPath>> {
-        self.get(&path.dataset)
-            .and_then(|ds| ds.list_objects(&path.partition))
-            .map(|keys| keys.into_iter().map(|k| path.object_path(&k)).collect())
+        let mut keys = self
+            .get(&path.dataset)
+            .and_then(|ds| ds.list_objects(&path.partition))?;
+        keys.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Ok(keys.into_iter().map(|k| path.object_path(&k)).collect())
+    }
+
+    /// Every dataset with its partitions and objects, sorted by path.
+    pub fn to_json(&self) -> Result<Value> {
+        let mut datasets = self.list_datasets();
+        datasets.sort_by_key(|path| path.to_string());
+
+        let mut datasets_json = vec![];
+        for dataset in datasets {
+            let mut partitions = self.list_partitions(&dataset)?;
+            partitions.sort_by(|a, b| a.partition.cmp(&b.partition));
+
+            let mut partitions_json = vec![];
+            for partition in partitions {
+                let objects = self.list_objects(&partition)?;
+                partitions_json.push(json!({
+                    "partition": partition.partition.to_string(),
+                    "objects": self.objects_to_json(&objects)?,
+                }));
+            }
+
+            datasets_json.push(json!({
+                "path": dataset.to_string(),
+                "partitions": partitions_json,
+            }));
+        }
+
+        Ok(json!({ "datasets": datasets_json }))
+    }
+
+    /// Objects along with their keys, sorted by key.
+    pub fn objects_to_json(&self, objects: &[ObjectPath]) -> Result<Value> {
+        let mut objects = objects.iter().collect::<Vec<&ObjectPath>>();
+        objects.sort_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+
+        let mut objects_json = vec![];
+        for object in objects {
+            let mut object_json = self.get_object(object)?.to_json();
+            object_json["key"] = json!(object.key.as_str());
+            objects_json.push(object_json);
+        }
+
+        Ok(Value::Array(objects_json))
+    }
+
+    /// Returns the state without its recorded changes, to be used as the base of a merge.
+    pub fn checkpoint(&self) -> Self {
+        State {
+            datasets: self.datasets.clone(),
+            changes: HashSet::new(),
+        }
+    }
+
+    /// Combines states produced by independent operations: the locations changed in `other`
+    /// since its checkpoint are copied over this state, datasets before partitions and objects.
+    pub fn merge(&self, other: &State) -> Self {
+        let mut new_state = self.clone();
+
+        for change in other.changes.iter() {
+            if let Change::Dataset(path) = change {
+                match other.datasets.get(path) {
+                    Some(dataset) => new_state.datasets.insert(path.clone(), dataset.clone()),
+                    None => new_state.datasets.remove(path),
+                };
+            }
+        }
+
+        for change in other.changes.iter() {
+            if let Change::Partition(path) = change {
+                match other.get_partition(path) {
+                    Ok(partition) => new_state
+                        .datasets
+                        .entry(path.dataset.clone())
+                        .or_default()
+                        .insert_partition(&path.partition, partition.clone()),
+                    Err(_) => {
+                        if let Ok(dataset) = new_state.get_mut(&path.dataset) {
+                            dataset.partitions.remove(&path.partition);
+                        }
+                    }
+                }
+            }
+        }
+
+        for change in other.changes.iter() {
+            if let Change::Object(path) = change {
+                match other.get_object(path) {
+                    Ok(object) => new_state
+                        .datasets
+                        .entry(path.dataset_path().clone())
+                        .or_default()
+                        .partitions
+                        .entry(path.get_partition().clone())
+                        .or_default()
+                        .insert_object(path.key.clone(), object.clone()),
+                    Err(_) => {
+                        let partition = new_state
+                            .get_mut(path.dataset_path())
+                            .and_then(|dataset| dataset.get_mut(path.get_partition()));
+                        if let Ok(partition) = partition {
+                            partition.objects.remove(&path.key);
+                        }
+                    }
+                }
+            }
+        }
+
+        new_state.changes = new_state.changes.union(other.changes.clone());
+        new_state
+    }
+
+    pub fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<Self> {
+        let mut new_state = self.clone();
+        let object_state = self.get_object(source)?.clone();
+
+        let target_dataset = new_state
+            .datasets
+            .entry(target.dataset_path().clone())
+            .or_default();
+        let target_partition = target_dataset
+            .partitions
+            .entry(target.get_partition().clone())
+            .or_default();
+        target_partition
+            .objects
+            .insert(target.key.clone(), object_state);
+
+        new_state.changes.insert(Change::Object(target.clone()));
+        Ok(new_state)
     }
 
     pub fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<Self> {
@@ -269,16 +447,33 @@ impl State {
         }
 
         {
-            let target_dataset = new_state.get_mut(target.dataset_path())?;
+            let target_dataset = new_state
+                .datasets
+                .entry(target.dataset_path().clone())
+                .or_default();
             let target_partition = target_dataset
                 .partitions
                 .entry(target.get_partition().clone())
-                .or_insert(PartitionState::default());
+                .or_default();
             target_partition
                 .objects
                 .insert(target.key.clone(), object_state);
         }
 
+        new_state.changes.insert(Change::Object(source.clone()));
+        new_state.changes.insert(Change::Object(target.clone()));
+        Ok(new_state)
+    }
+
+    pub fn remove_dataset(&self, path: &DatasetPath) -> Result<Self> {
+        let mut new_state = self.clone();
+
+        new_state
+            .datasets
+            .remove(path)
+            .ok_or_else(|| StateError::MissingDataset(path.clone()))?;
+
+        new_state.changes.insert(Change::Dataset(path.clone()));
         Ok(new_state)
     }
 
@@ -288,15 +483,17 @@ impl State {
         let dataset = new_state.get_mut(&path.dataset)?;
         dataset.remove_partition(&path.partition)?;
 
+        new_state.changes.insert(Change::Partition(path.clone()));
         Ok(new_state)
     }
 
     pub fn remove_object(&self, path: &ObjectPath) -> Result<Self> {
         let mut new_state = self.clone();
 
-        let dataset = new_state.get_mut(&path.dataset_path())?;
-        dataset.remove_object(&path.get_partition(), &path.key)?;
+        let dataset = new_state.get_mut(path.dataset_path())?;
+        dataset.remove_object(path.get_partition(), &path.key)?;
 
+        new_state.changes.insert(Change::Object(path.clone()));
         Ok(new_state)
     }
 
@@ -305,6 +502,7 @@ impl State {
 
         new_state.datasets.insert(path.clone(), state);
 
+        new_state.changes.insert(Change::Dataset(path.clone()));
         Ok(new_state)
     }
 
@@ -314,20 +512,35 @@ impl State {
         let dataset = new_state.get_mut(&path.dataset)?;
         dataset.insert_partition(&path.partition, state);
 
+        new_state.changes.insert(Change::Partition(path.clone()));
         Ok(new_state)
     }
 
+    /// Inserts an object, creating its dataset and partition if they do not exist yet.
     pub fn insert_object(&self, path: &ObjectPath, state: ObjectState) -> Result<Self> {
         let mut new_state = self.clone();
 
-        let dataset = new_state.get_mut(&path.dataset_path())?;
-        let partition = dataset.get_mut(&path.get_partition())?;
+        let dataset = new_state
+            .datasets
+            .entry(path.dataset_path().clone())
+            .or_default();
+        let partition = dataset
+            .partitions
+            .entry(path.get_partition().clone())
+            .or_default();
         partition.insert_object(path.key.clone(), state);
 
+        new_state.changes.insert(Change::Object(path.clone()));
         Ok(new_state)
     }
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "State:")?;
@@ -1,15 +1,22 @@
+use std::env;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Error, Result};
 use parquet::errors::ParquetError;
+use parquet::file::reader::ChunkReader;
+use parquet::file::writer::ParquetWriter;
 use thiserror::Error;
 
 use crate::base::{Bytes, Format, ObjectKey, Partition, ToStdPath};
+use crate::compress::Compression;
+use crate::generate::Generator;
 use crate::csv::Csv;
 use crate::parquet::Parquet;
 use crate::path::{DatasetPath, ObjectPath, PartitionPath};
+use crate::repartition::Partitioner;
+use crate::sample::Sampler;
 use crate::state::ObjectState;
 
 #[derive(Debug, Clone)]
@@ -34,17 +41,178 @@ pub enum StoreError {
 
     #[error("Invalid partition name: {0}")]
     InvalidPartition(String),
+
+    #[error("Missing dataset: {0}")]
+    MissingDataset(DatasetPath),
+
+    #[error("Cannot read the rows of compressed object: {0}")]
+    CompressedObject(ObjectPath),
+
+    #[error("Cannot compress object: {0}")]
+    CannotCompress(ObjectPath),
+
+    #[error("Cannot convert object: {0} to {1}")]
+    CannotConvert(ObjectPath, ObjectPath),
+
+    #[error("Objects of different formats: {0} and {1}")]
+    MixedFormats(ObjectPath, ObjectPath),
+}
+
+impl StoreError {
+    /// Whether the operation may succeed if tried again, only interrupted IO may.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            StoreError::Io(error) => is_transient_io(error),
+            _ => false,
+        }
+    }
+}
+
+/// IO errors caused by the conditions of the moment rather than by the operation itself.
+pub fn is_transient_io(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+    )
 }
 
-fn as_err<T, E: Into<StoreError>>(error: E) -> Result<T> {
+pub(crate) fn as_err<T, E: Into<StoreError>>(error: E) -> Result<T> {
     Err(Error::new(error.into()))
 }
 
-pub trait Store {
+/// Format shared by objects read together, e.g. the inputs of a repartition.
+pub(crate) fn common_format(paths: &[ObjectPath]) -> Result<Format> {
+    let format = match paths[0].infer_format() {
+        Some(format) => format,
+        None => return as_err(StoreError::CannotInferSchema(paths[0].clone())),
+    };
+
+    match paths.iter().find(|path| path.infer_format().as_ref() != Some(&format)) {
+        Some(path) => as_err(StoreError::MixedFormats(paths[0].clone(), path.clone())),
+        None => Ok(format),
+    }
+}
+
+/// Fails on compressed objects, whose rows cannot be read by the format readers.
+pub(crate) fn check_uncompressed(paths: &[ObjectPath]) -> Result<()> {
+    match paths.iter().find(|path| path.key.is_gzipped()) {
+        Some(path) => as_err(StoreError::CompressedObject(path.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Walks nested `key=value` directories, a directory without sub-partitions is a partition.
+/// `children` lists the entries of a directory, along with whether they are directories.
+pub(crate) fn list_partition_dirs<F>(
+    path: &Path,
+    relative: PathBuf,
+    children: &F,
+) -> Result<Vec<Partition>>
+where
+    F: Fn(&Path) -> Result<Vec<(String, bool)>>,
+{
+    let mut partitions = vec![];
+
+    for (name, is_dir) in children(path)? {
+        if !is_dir {
+            match relative.as_os_str().is_empty() {
+                true => return as_err(StoreError::InvalidPartition(name)),
+                false => continue,
+            }
+        }
+
+        let mut partition_dir = relative.clone();
+        partition_dir.push(&name);
+
+        let nested = list_partition_dirs(&path.join(&name), partition_dir.clone(), children)?;
+
+        if nested.is_empty() {
+            let name = partition_dir.to_string_lossy().to_string();
+            match name.parse() {
+                Ok(partition) => partitions.push(partition),
+                Err(_) => return as_err(StoreError::InvalidPartition(name)),
+            }
+        } else {
+            partitions.extend(nested);
+        }
+    }
+
+    Ok(partitions)
+}
+
+pub(crate) fn read_object_state<R: 'static + io::Read + io::Seek + ChunkReader>(
+    path: &ObjectPath,
+    reader: R,
+) -> Result<ObjectState> {
+    match path.infer_format() {
+        Some(Format::Csv) if path.key.is_gzipped() => Csv::read_gzipped_object_state(reader),
+        Some(Format::Csv) => Csv::read_object_state(reader),
+        Some(Format::Parquet) => Parquet::read_object_state(&reader),
+        None => as_err(StoreError::CannotInferSchema(path.clone())),
+    }
+}
+
+/// Combines objects of one format into `writers`, CSV objects up to a size and Parquet objects
+/// up to a number of rows. `written` tells the size written so far into a writer.
+pub(crate) fn combine_objects<R, W>(
+    input_paths: &[ObjectPath],
+    readers: Vec<R>,
+    writers: Vec<W>,
+    target: &RebalanceTarget,
+    written: Box<dyn Fn(usize) -> Result<Bytes>>,
+) -> Result<()>
+where
+    R: 'static + io::Read + io::Seek + ChunkReader,
+    W: 'static + ParquetWriter,
+{
+    match (common_format(input_paths)?, target.clone()) {
+        (Format::Csv, RebalanceTarget::Size(size)) => {
+            let is_writer_full = Box::new(move |idx| Ok(written(idx)? >= size.mul(0.9)));
+            Csv::combine_objects(readers, writers, is_writer_full)
+        }
+        (Format::Parquet, RebalanceTarget::Rows(rows)) => {
+            Parquet::combine_objects(readers, writers, rows)
+        }
+        (format, _) => as_err(StoreError::CannotCombineFormatAndTarget(format, target.clone())),
+    }
+}
+
+/// Splits the rows of objects of one format into a writer per partition.
+pub(crate) fn partition_objects<R, W>(
+    format: &Format,
+    readers: Vec<R>,
+    partitioner: &Partitioner,
+    create_writer: impl FnMut(&Partition) -> Result<W>,
+) -> Result<()>
+where
+    R: 'static + io::Read + io::Seek + ChunkReader,
+    W: 'static + ParquetWriter,
+{
+    match format {
+        Format::Csv => Csv::partition_objects(readers, partitioner, create_writer),
+        Format::Parquet => Parquet::partition_objects(readers, partitioner, create_writer),
+    }
+}
+
+pub trait Store: Send + Sync {
     fn read_object(&self, path: &ObjectPath) -> Result<ObjectState>;
+    /// Reader of the content of an object, e.g. to copy it into another store.
+    fn open_object(&self, path: &ObjectPath) -> Result<Box<dyn io::Read + Send>>;
+    /// Writes the content of `reader` into an object, creating its partition if needed.
+    fn write_object(&self, path: &ObjectPath, reader: &mut dyn io::Read) -> Result<()>;
+    fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()>;
     fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()>;
+    fn supports_move(&self) -> bool;
     fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<Partition>>;
     fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectKey>>;
+    fn remove_dataset(&self, path: &DatasetPath) -> Result<()>;
     fn remove_partition(&self, path: &PartitionPath) -> Result<()>;
     fn remove_object(&self, path: &ObjectPath) -> Result<()>;
     fn rebalance_objects(
@@ -53,6 +221,31 @@ pub trait Store {
         output_paths: &[ObjectPath],
         target: &RebalanceTarget,
     ) -> Result<Vec<ObjectState>>;
+    fn sample_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        sampler: &Sampler,
+    ) -> Result<ObjectState>;
+    fn repartition_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        target: &DatasetPath,
+        partitioner: &Partitioner,
+    ) -> Result<Vec<(ObjectPath, ObjectState)>>;
+    fn compress_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        compression: &Compression,
+    ) -> Result<ObjectState>;
+    fn generate_object(
+        &self,
+        path: &ObjectPath,
+        generator: &Generator,
+        rows: usize,
+    ) -> Result<ObjectState>;
+    fn convert_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<ObjectState>;
 }
 
 pub struct FileStore {
@@ -70,11 +263,34 @@ impl FileStore {
         buf
     }
 
-    fn read_object_state(path: &ObjectPath, file: fs::File) -> Result<ObjectState> {
-        match path.infer_format() {
-            Some(Format::Csv) => Csv::read_object_state(file),
-            Some(Format::Parquet) => Parquet::read_object_state(&file),
-            None => as_err(StoreError::CannotInferSchema(path.clone())),
+    /// Names of the entries of a directory, along with whether they are directories.
+    fn children(fs_path: &Path) -> Result<Vec<(String, bool)>> {
+        fs::read_dir(fs_path)?
+            .map(|dir_entry| {
+                let entry_path = dir_entry?.path();
+                match entry_path.file_name() {
+                    Some(f) => Ok((f.to_string_lossy().to_string(), entry_path.is_dir())),
+                    None => as_err(StoreError::InvalidPartition("".to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Removes a directory unless it is not empty, returns whether it was removed. Sibling
+    /// partitions removed concurrently race on their parent, whoever loses finds it gone or
+    /// still holding the other partition, and leaves it to the winner.
+    fn remove_empty_dir(fs_path: &Path) -> Result<bool> {
+        match fs::remove_dir(fs_path) {
+            Ok(()) => Ok(true),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::DirectoryNotEmpty
+                ) =>
+            {
+                Ok(false)
+            }
+            Err(error) => Err(error.into()),
         }
     }
 }
@@ -83,7 +299,37 @@ impl Store for FileStore {
     fn read_object(&self, path: &ObjectPath) -> Result<ObjectState> {
         let fs_path = self.fs_path(path.std_path());
         let file = fs::File::open(fs_path)?;
-        Self::read_object_state(path, file)
+        read_object_state(path, file)
+    }
+
+    fn open_object(&self, path: &ObjectPath) -> Result<Box<dyn io::Read + Send>> {
+        let file = fs::File::open(self.fs_path(path.std_path()))
+            .with_context(|| format!("object to open not found: {}", path))?;
+        Ok(Box::new(file))
+    }
+
+    fn write_object(&self, path: &ObjectPath, reader: &mut dyn io::Read) -> Result<()> {
+        let fs_path = self.fs_path(path.std_path());
+        let fs_partition = self.fs_path(path.partition_path().std_path());
+
+        fs::create_dir_all(fs_partition)
+            .with_context(|| format!("cannot create partition: {}", path.partition_path()))?;
+        let mut file = fs::File::create(&fs_path)
+            .with_context(|| format!("failed to create object: {}", path))?;
+        io::copy(reader, &mut file).with_context(|| format!("cannot write object: {}", path))?;
+        Ok(())
+    }
+
+    fn copy_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
+        let fs_source = self.fs_path(source.std_path());
+        let fs_target = self.fs_path(target.std_path());
+        let fs_target_part = self.fs_path(target.partition_path().std_path());
+
+        fs::create_dir_all(fs_target_part)
+            .with_context(|| format!("cannot create partition: {}", target.partition_path()))?;
+        fs::copy(fs_source, fs_target)
+            .with_context(|| format!("cannot copy {} to {}", source, target))?;
+        Ok(())
     }
 
     fn move_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<()> {
@@ -98,38 +344,18 @@ impl Store for FileStore {
         Ok(())
     }
 
+    fn supports_move(&self) -> bool {
+        true
+    }
+
     fn list_partitions(&self, path: &DatasetPath) -> Result<Vec<Partition>> {
         let fs_path = self.fs_path(path.std_path());
 
         if !fs_path.is_dir() {
-            return as_err(io::Error::new(io::ErrorKind::NotFound, "not a directory"));
+            return as_err(StoreError::MissingDataset(path.clone()));
         }
 
-        //FIXME: Support depth > 1
-
-        let partitions = fs::read_dir(self.fs_path(path.std_path()))?
-            .map(|dir_entry| {
-                let path = dir_entry?.path();
-                let file_name = match path.file_name() {
-                    Some(f) => f.to_string_lossy().to_string(),
-                    None => {
-                        return as_err(StoreError::InvalidPartition("".to_string()));
-                    }
-                };
-
-                let partition = match (file_name.find('='), file_name.ends_with('=')) {
-                    (Some(idx), false) => Partition::new(
-                        file_name[0..idx].to_string(),
-                        file_name[idx + 1..].to_string(),
-                    ),
-                    _ => return as_err(StoreError::InvalidPartition(file_name)),
-                };
-
-                Ok(partition)
-            })
-            .collect::<Result<Vec<Partition>>>()?;
-
-        Ok(partitions)
+        list_partition_dirs(&fs_path, PathBuf::new(), &Self::children)
     }
 
     fn list_objects(&self, path: &PartitionPath) -> Result<Vec<ObjectKey>> {
@@ -151,9 +377,25 @@ impl Store for FileStore {
             .collect::<Result<Vec<ObjectKey>>>()
     }
 
-    fn remove_partition(&self, path: &PartitionPath) -> Result<()> {
+    fn remove_dataset(&self, path: &DatasetPath) -> Result<()> {
         fs::remove_dir(self.fs_path(path.std_path()))
+            .with_context(|| format!("dataset to remove not found: {}", path))?;
+        Ok(())
+    }
+
+    fn remove_partition(&self, path: &PartitionPath) -> Result<()> {
+        let mut fs_path = self.fs_path(path.std_path());
+        fs::remove_dir(&fs_path)
             .with_context(|| format!("partition to remove not found: {}", path))?;
+
+        // Nested partitions leave their parent directories behind once empty
+        let fs_dataset = self.fs_path(path.dataset.std_path());
+        while fs_path.pop() && fs_path.starts_with(&fs_dataset) && fs_path != fs_dataset {
+            if !Self::remove_empty_dir(&fs_path)? {
+                break;
+            }
+        }
+
         Ok(())
     }
 
@@ -169,6 +411,8 @@ impl Store for FileStore {
         output_paths: &[ObjectPath],
         target: &RebalanceTarget,
     ) -> Result<Vec<ObjectState>> {
+        check_uncompressed(input_paths)?;
+
         let input_files = input_paths
             .iter()
             .map(|path| {
@@ -188,29 +432,230 @@ impl Store for FileStore {
             })
             .collect::<Result<Vec<fs::File>>>()?;
 
-        match (input_paths[0].infer_format(), target.clone()) {
-            (Some(Format::Csv), RebalanceTarget::Size(size)) => {
-                let paths: Vec<PathBuf> = output_paths.iter().map(|path| self.fs_path(path.std_path())).collect();
-                Csv::combine_objects(input_files, output_files, Box::new(move |idx| {
-                    Bytes::new(fs::metadata(&paths[idx]).unwrap().len() as usize) >= size.mul(0.9)
-                }))
-            }
-            (Some(Format::Parquet), RebalanceTarget::Rows(rows)) => {
-                Parquet::combine_objects(input_files, output_files, rows)
-            }
-            (Some(format), _) => as_err(StoreError::CannotCombineFormatAndTarget(format.clone(), target.clone())),
-            (None, _) => as_err(StoreError::CannotInferSchema(input_paths[0].clone())),
-        }?;
+        let paths: Vec<PathBuf> =
+            output_paths.iter().map(|path| self.fs_path(path.std_path())).collect();
+        let written = Box::new(move |idx| {
+            Ok(Bytes::new(fs::metadata(&paths[idx])?.len() as usize))
+        });
+        combine_objects(input_paths, input_files, output_files, target, written)?;
 
         let states = output_paths
             .iter()
             .map(|path| {
                 let file = fs::File::open(self.fs_path(path.std_path()))
                     .with_context(|| format!("rebalanced object not found: {}", path))?;
-                Self::read_object_state(path, file)
+                read_object_state(path, file)
             })
             .collect::<Result<Vec<ObjectState>>>()?;
 
         Ok(states)
     }
+
+    fn sample_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        sampler: &Sampler,
+    ) -> Result<ObjectState> {
+        check_uncompressed(std::slice::from_ref(source))?;
+
+        let fs_target = self.fs_path(target.std_path());
+        let fs_target_part = self.fs_path(target.partition_path().std_path());
+
+        let input = fs::File::open(self.fs_path(source.std_path()))
+            .with_context(|| format!("sample input object not found: {}", source))?;
+        fs::create_dir_all(fs_target_part)
+            .with_context(|| format!("cannot create partition: {}", target.partition_path()))?;
+        let output = fs::File::create(&fs_target)
+            .with_context(|| format!("failed to create sample object: {}", target))?;
+
+        let filter = sampler.object_filter(source);
+        match source.infer_format() {
+            Some(Format::Csv) => Csv::filter_object(input, output, filter),
+            Some(Format::Parquet) => Parquet::filter_object(input, output, filter),
+            None => as_err(StoreError::CannotInferSchema(source.clone())),
+        }?;
+
+        let file = fs::File::open(&fs_target)
+            .with_context(|| format!("sample object not found: {}", target))?;
+        read_object_state(target, file)
+    }
+
+    fn repartition_objects(
+        &self,
+        input_paths: &[ObjectPath],
+        target: &DatasetPath,
+        partitioner: &Partitioner,
+    ) -> Result<Vec<(ObjectPath, ObjectState)>> {
+        check_uncompressed(input_paths)?;
+
+        let input_files = input_paths
+            .iter()
+            .map(|path| {
+                let file = fs::File::open(self.fs_path(path.std_path()))
+                    .with_context(|| format!("repartition input object not found: {}", path))?;
+                Ok(file)
+            })
+            .collect::<Result<Vec<fs::File>>>()?;
+
+        let format = common_format(input_paths)?;
+
+        let mut output_paths = vec![];
+        let create_output = |partition: &Partition| {
+            let path = target.object_path(partition, &ObjectKey::new(format!("0.{}", format)));
+            let fs_path = self.fs_path(path.std_path());
+
+            fs::create_dir_all(self.fs_path(path.partition_path().std_path()))
+                .with_context(|| format!("cannot create partition: {}", path.partition_path()))?;
+            let file = fs::File::create(&fs_path)
+                .with_context(|| format!("failed to create repartition output object: {}", path))?;
+
+            output_paths.push(path);
+            Ok(file)
+        };
+
+        partition_objects(&format, input_files, partitioner, create_output)?;
+
+        output_paths
+            .into_iter()
+            .map(|path| {
+                let file = fs::File::open(self.fs_path(path.std_path()))
+                    .with_context(|| format!("repartitioned object not found: {}", path))?;
+                let state = read_object_state(&path, file)?;
+                Ok((path, state))
+            })
+            .collect()
+    }
+
+    fn compress_object(
+        &self,
+        source: &ObjectPath,
+        target: &ObjectPath,
+        compression: &Compression,
+    ) -> Result<ObjectState> {
+        let fs_target = self.fs_path(target.std_path());
+        let fs_target_part = self.fs_path(target.partition_path().std_path());
+
+        // Written next to the target first, the source and target can be the same object
+        let mut fs_tmp = fs_target.clone().into_os_string();
+        fs_tmp.push(".tmp");
+
+        let input = fs::File::open(self.fs_path(source.std_path()))
+            .with_context(|| format!("object to compress not found: {}", source))?;
+        fs::create_dir_all(fs_target_part)
+            .with_context(|| format!("cannot create partition: {}", target.partition_path()))?;
+        let output = fs::File::create(&fs_tmp)
+            .with_context(|| format!("failed to create compressed object: {}", target))?;
+
+        let result = match (source.infer_format(), compression.csv_level) {
+            (Some(Format::Parquet), _) => {
+                Parquet::compress_object(input, output, compression.codec)
+            }
+            (Some(Format::Csv), Some(level)) if !source.key.is_gzipped() => {
+                Csv::gzip_object(input, output, level)
+            }
+            _ => as_err(StoreError::CannotCompress(source.clone())),
+        };
+        if let Err(error) = result {
+            fs::remove_file(&fs_tmp)?;
+            return Err(error);
+        }
+
+        fs::rename(&fs_tmp, &fs_target)
+            .with_context(|| format!("cannot rename compressed object to {}", target))?;
+        let file = fs::File::open(&fs_target)
+            .with_context(|| format!("compressed object not found: {}", target))?;
+        read_object_state(target, file)
+    }
+
+    fn generate_object(
+        &self,
+        path: &ObjectPath,
+        generator: &Generator,
+        rows: usize,
+    ) -> Result<ObjectState> {
+        if path.key.is_gzipped() {
+            return as_err(StoreError::CompressedObject(path.clone()));
+        }
+
+        let fs_path = self.fs_path(path.std_path());
+        let fs_partition = self.fs_path(path.partition_path().std_path());
+
+        fs::create_dir_all(fs_partition)
+            .with_context(|| format!("cannot create partition: {}", path.partition_path()))?;
+        let output = fs::File::create(&fs_path)
+            .with_context(|| format!("failed to create generated object: {}", path))?;
+
+        let batches = generator.batches(rows);
+        match path.infer_format() {
+            Some(Format::Csv) => Csv::write_object(output, batches),
+            Some(Format::Parquet) => Parquet::write_object(output, generator.schema(), batches),
+            None => as_err(StoreError::CannotInferSchema(path.clone())),
+        }?;
+
+        let file = fs::File::open(&fs_path)
+            .with_context(|| format!("generated object not found: {}", path))?;
+        read_object_state(path, file)
+    }
+
+    fn convert_object(&self, source: &ObjectPath, target: &ObjectPath) -> Result<ObjectState> {
+        check_uncompressed(&[source.clone(), target.clone()])?;
+
+        let format = match (source.infer_format(), target.infer_format()) {
+            (Some(source_format), Some(target_format)) if source_format != target_format => {
+                target_format
+            }
+            _ => return as_err(StoreError::CannotConvert(source.clone(), target.clone())),
+        };
+
+        let fs_target = self.fs_path(target.std_path());
+        let fs_target_part = self.fs_path(target.partition_path().std_path());
+
+        let input = fs::File::open(self.fs_path(source.std_path()))
+            .with_context(|| format!("object to convert not found: {}", source))?;
+        fs::create_dir_all(fs_target_part)
+            .with_context(|| format!("cannot create partition: {}", target.partition_path()))?;
+        let output = fs::File::create(&fs_target)
+            .with_context(|| format!("failed to create converted object: {}", target))?;
+
+        match format {
+            Format::Parquet => Csv::convert_object(input, output),
+            Format::Csv => Parquet::convert_object(input, output),
+        }?;
+
+        let file = fs::File::open(&fs_target)
+            .with_context(|| format!("converted object not found: {}", target))?;
+        read_object_state(target, file)
+    }
+}
+
+/// Local copy of the objects an operation rewrites, so that the file store does the rewriting
+/// before its outputs are copied back into their own store. The copy is removed once dropped.
+pub(crate) struct Scratch {
+    root: PathBuf,
+    pub(crate) store: FileStore,
+}
+
+impl Scratch {
+    /// Directory named after the store using it, `id` telling apart its concurrent operations.
+    pub(crate) fn new(name: &str, id: usize) -> Result<Self> {
+        let root = env::temp_dir().join(format!("osm-{}-{}-{}", name, std::process::id(), id));
+        fs::create_dir_all(&root)
+            .with_context(|| format!("cannot create scratch directory: {}", root.display()))?;
+
+        Ok(Self {
+            store: FileStore::new(root.clone()),
+            root,
+        })
+    }
+
+    pub(crate) fn fs_path(&self, path: &ObjectPath) -> PathBuf {
+        self.root.join(path.std_path())
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
 }
@@ -1,12 +1,18 @@
+use std::collections::BTreeMap;
+
 use anyhow::Result;
+use serde_json::{json, Value};
 
-use crate::path::{DatasetPath, PartitionPath};
+use crate::base::{Bytes, Partition};
+use crate::path::{DatasetPath, ObjectPath, PartitionPath};
 use crate::state::State;
 
 pub trait View {
     fn render(&self, state: &State) -> Result<String>;
+    fn render_json(&self, state: &State) -> Result<Value>;
 }
 
+/// Partitions of a dataset, rendered as missing if it is not in the state, e.g. once removed.
 pub struct ListPartitions {
     path: DatasetPath,
     with_objects: bool,
@@ -21,8 +27,15 @@ impl ListPartitions {
 impl View for ListPartitions {
     fn render(&self, state: &State) -> Result<String> {
         let mut out = format!("List Partitions for \"{}\":", self.path);
+        if !state.contains_dataset(&self.path) {
+            out.push_str(" missing");
+            return Ok(out);
+        }
 
-        for partition in state.list_partitions(&self.path)? {
+        let mut partitions = state.list_partitions(&self.path)?;
+        partitions.sort_by(|a, b| a.partition.cmp(&b.partition));
+
+        for partition in partitions {
             let objects = state.list_objects(&partition)?;
             let size = state.get_partition(&partition)?.size();
             out.push_str(&format!(
@@ -44,6 +57,32 @@ impl View for ListPartitions {
 
         Ok(out)
     }
+
+    fn render_json(&self, state: &State) -> Result<Value> {
+        if !state.contains_dataset(&self.path) {
+            return Ok(json!({"dataset": self.path.to_string(), "missing": true, "partitions": []}));
+        }
+
+        let mut partitions = state.list_partitions(&self.path)?;
+        partitions.sort_by(|a, b| a.partition.cmp(&b.partition));
+
+        let mut partitions_json = vec![];
+        for partition in partitions {
+            let objects = state.list_objects(&partition)?;
+            let mut partition_json = json!({
+                "partition": partition.partition.to_string(),
+                "object_count": objects.len(),
+                "size": state.get_partition(&partition)?.size().as_usize(),
+            });
+
+            if self.with_objects {
+                partition_json["objects"] = state.objects_to_json(&objects)?;
+            }
+            partitions_json.push(partition_json);
+        }
+
+        Ok(json!({"dataset": self.path.to_string(), "partitions": partitions_json}))
+    }
 }
 
 pub struct ListObjects {
@@ -71,4 +110,310 @@ impl View for ListObjects {
 
         Ok(out)
     }
+
+    fn render_json(&self, state: &State) -> Result<Value> {
+        let objects = state.objects_to_json(&state.list_objects(&self.path)?)?;
+        Ok(json!({"partition": self.path.to_string(), "objects": objects}))
+    }
+}
+
+/// Total size, rows and average object size of some partitions of a dataset, and with
+/// `detailed` the size of every partition and object along with a histogram of object sizes.
+pub struct SizeOf {
+    path: DatasetPath,
+    partitions: Vec<Partition>,
+    detailed: bool,
+}
+
+impl SizeOf {
+    /// Smallest bucket of the histogram, every bucket after it being twice as large.
+    const MIN_BUCKET: usize = 1024;
+    const BAR_WIDTH: usize = 40;
+
+    /// Sizes every partition of the dataset if `partitions` is empty.
+    pub fn new(path: DatasetPath, partitions: Vec<Partition>, detailed: bool) -> Self {
+        Self {
+            path,
+            partitions,
+            detailed,
+        }
+    }
+
+    fn partitions(&self, state: &State) -> Result<Vec<PartitionPath>> {
+        let mut partitions = match self.partitions.is_empty() {
+            true => state.list_partitions(&self.path)?,
+            false => self
+                .partitions
+                .iter()
+                .map(|partition| self.path.partition_path(partition))
+                .collect(),
+        };
+        partitions.sort_by(|a, b| a.partition.cmp(&b.partition));
+        Ok(partitions)
+    }
+
+    fn totals(&self, state: &State) -> Result<Totals> {
+        let mut totals = Totals::new();
+
+        for partition in self.partitions(state)? {
+            for object_path in state.list_objects(&partition)? {
+                let object = state.get_object(&object_path)?;
+                totals.add(object.num_rows(), object.size);
+            }
+        }
+
+        Ok(totals)
+    }
+
+    /// Number of objects by the upper bound of their size, in powers of two.
+    fn histogram(&self, state: &State) -> Result<BTreeMap<usize, usize>> {
+        let mut histogram = BTreeMap::new();
+
+        for partition in self.partitions(state)? {
+            for object_path in state.list_objects(&partition)? {
+                let size = state.get_object(&object_path)?.size.as_usize();
+                let bucket = size.next_power_of_two().max(Self::MIN_BUCKET);
+                *histogram.entry(bucket).or_insert(0) += 1;
+            }
+        }
+
+        Ok(histogram)
+    }
+}
+
+impl View for SizeOf {
+    fn render(&self, state: &State) -> Result<String> {
+        let mut out = format!("Size of \"{}\":", self.path);
+
+        let totals = self.totals(state)?;
+        out.push_str(&format!(
+            "\n  objects: {}, size: {}, average object size: {}",
+            totals.objects,
+            totals.size,
+            totals.average_size()
+        ));
+        out.push_str(&format!("\n  rows: {}", totals.rows_to_string()));
+
+        if !self.detailed {
+            return Ok(out);
+        }
+
+        out.push_str("\nPartitions:");
+        for partition in self.partitions(state)? {
+            let objects = state.list_objects(&partition)?;
+
+            let mut partition_totals = Totals::new();
+            for object_path in &objects {
+                let object = state.get_object(object_path)?;
+                partition_totals.add(object.num_rows(), object.size);
+            }
+            out.push_str(&format!(
+                "\n  - {} (objects: {}, size: {}, rows: {})",
+                partition.partition,
+                partition_totals.objects,
+                partition_totals.size,
+                partition_totals.rows_to_string()
+            ));
+
+            for object_path in &objects {
+                let object = state.get_object(object_path)?;
+                let rows = object
+                    .num_rows()
+                    .map_or_else(|| "unknown".to_string(), |rows| rows.to_string());
+                out.push_str(&format!(
+                    "\n    - {} (size: {}, rows: {})",
+                    object_path.key, object.size, rows
+                ));
+            }
+        }
+
+        let histogram = self.histogram(state)?;
+        let max_count = histogram.values().copied().max().unwrap_or(0);
+        out.push_str("\nHistogram:");
+        for (bucket, count) in histogram {
+            let width = (count * Self::BAR_WIDTH).div_ceil(max_count);
+            out.push_str(&format!(
+                "\n  <= {:>10}: {:>6} {}",
+                Bytes::new(bucket).to_string(),
+                count,
+                "#".repeat(width)
+            ));
+        }
+
+        Ok(out)
+    }
+
+    fn render_json(&self, state: &State) -> Result<Value> {
+        let totals = self.totals(state)?;
+        let mut out = json!({
+            "dataset": self.path.to_string(),
+            "object_count": totals.objects,
+            "size": totals.size.as_usize(),
+            "average_object_size": totals.average_size().as_usize(),
+            "num_rows": totals.rows,
+            "unknown_rows_object_count": totals.unknown_rows,
+        });
+
+        if !self.detailed {
+            return Ok(out);
+        }
+
+        let mut partitions_json = vec![];
+        for partition in self.partitions(state)? {
+            let objects = state.list_objects(&partition)?;
+            partitions_json.push(json!({
+                "partition": partition.partition.to_string(),
+                "size": state.get_partition(&partition)?.size().as_usize(),
+                "objects": state.objects_to_json(&objects)?,
+            }));
+        }
+        out["partitions"] = Value::Array(partitions_json);
+
+        out["histogram"] = self
+            .histogram(state)?
+            .into_iter()
+            .map(|(bucket, count)| json!({"max_size": bucket, "object_count": count}))
+            .collect();
+
+        Ok(out)
+    }
+}
+
+struct Totals {
+    objects: usize,
+    size: Bytes,
+    rows: usize,
+    unknown_rows: usize,
+}
+
+impl Totals {
+    fn new() -> Self {
+        Self {
+            objects: 0,
+            size: Bytes::new(0),
+            rows: 0,
+            unknown_rows: 0,
+        }
+    }
+
+    fn add(&mut self, rows: Option<usize>, size: Bytes) {
+        self.objects += 1;
+        self.size = self.size + size;
+        match rows {
+            Some(rows) => self.rows += rows,
+            None => self.unknown_rows += 1,
+        }
+    }
+
+    /// Rows are only known for Parquet objects.
+    fn rows_to_string(&self) -> String {
+        match self.unknown_rows {
+            0 => self.rows.to_string(),
+            unknown if unknown == self.objects => "unknown".to_string(),
+            unknown => format!("{} (unknown for {} objects)", self.rows, unknown),
+        }
+    }
+
+    fn average_size(&self) -> Bytes {
+        match self.objects {
+            0 => Bytes::new(0),
+            objects => Bytes::new(self.size.as_usize() / objects),
+        }
+    }
+}
+
+/// Sizes of objects before and after they were compressed.
+pub struct CompressionReport {
+    objects: Vec<(ObjectPath, ObjectPath, Bytes)>,
+}
+
+impl CompressionReport {
+    /// Takes the compressed objects, the objects they are written to and their size before.
+    pub fn new(mut objects: Vec<(ObjectPath, ObjectPath, Bytes)>) -> Self {
+        objects.sort_by_key(|(_, target, _)| target.to_string());
+        Self { objects }
+    }
+
+    /// Size of every object after, None if it is missing e.g. when its compression failed.
+    fn sizes_after(&self, state: &State) -> Vec<Option<Bytes>> {
+        self.objects
+            .iter()
+            .map(|(_, target, _)| state.get_object(target).ok().map(|object| object.size))
+            .collect()
+    }
+
+    fn totals(&self, sizes_after: &[Option<Bytes>]) -> (Bytes, Bytes) {
+        let before = self
+            .objects
+            .iter()
+            .fold(Bytes::new(0), |total, (_, _, size)| total + *size);
+        let after = sizes_after
+            .iter()
+            .flatten()
+            .fold(Bytes::new(0), |total, size| total + *size);
+        (before, after)
+    }
+}
+
+impl View for CompressionReport {
+    fn render(&self, state: &State) -> Result<String> {
+        let mut out = "Compression Report:".to_string();
+        let sizes_after = self.sizes_after(state);
+
+        for ((_, target, before), after) in self.objects.iter().zip(&sizes_after) {
+            match after {
+                Some(after) => out.push_str(&format!(
+                    "\n  - {} (size: {} -> {}, {:.1}%)",
+                    target,
+                    before,
+                    after,
+                    ratio(*before, *after)
+                )),
+                None => out.push_str(&format!("\n  - {} (size: {} -> missing)", target, before)),
+            }
+        }
+
+        let (before, after) = self.totals(&sizes_after);
+        out.push_str(&format!(
+            "\nTotal: {} -> {}, {:.1}%",
+            before,
+            after,
+            ratio(before, after)
+        ));
+
+        Ok(out)
+    }
+
+    fn render_json(&self, state: &State) -> Result<Value> {
+        let sizes_after = self.sizes_after(state);
+
+        let objects = self
+            .objects
+            .iter()
+            .zip(&sizes_after)
+            .map(|((source, target, before), after)| {
+                json!({
+                    "source": source.to_string(),
+                    "target": target.to_string(),
+                    "before": before.as_usize(),
+                    "after": after.map(|size| size.as_usize()),
+                })
+            })
+            .collect::<Vec<Value>>();
+
+        let (before, after) = self.totals(&sizes_after);
+        Ok(json!({
+            "objects": objects,
+            "before": before.as_usize(),
+            "after": after.as_usize(),
+        }))
+    }
+}
+
+/// Size after as a percentage of the size before.
+fn ratio(before: Bytes, after: Bytes) -> f64 {
+    match before.as_usize() {
+        0 => 100.0,
+        before => after.as_usize() as f64 * 100.0 / before as f64,
+    }
 }
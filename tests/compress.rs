@@ -0,0 +1,51 @@
+use std::fs;
+use std::process;
+
+use osm::compress::{Codec, Compression};
+use osm::generate::Generator;
+use osm::job::{Compress, Generate, Job};
+use osm::runtime::{Execution, Runtime};
+use osm::state::State;
+use osm::store::FileStore;
+
+fn execute(runtime: &Runtime, state: &State, job: &dyn Job) -> Execution {
+    let execution = runtime.execute(state, job.actions(state).unwrap());
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution
+}
+
+#[test]
+fn compress_generated_object() {
+    let root = std::env::temp_dir().join(format!("osm-compress-{}", process::id()));
+    let runtime = Runtime::new(Box::new(FileStore::new(root.clone())), 2);
+
+    let generator = Generator::new(
+        "id:int;name:string".parse().unwrap(),
+        "0%".parse().unwrap(),
+        7,
+    );
+    let generate = Generate::new("file://b/d/p=1/0.parquet".parse().unwrap(), generator, 5000);
+    let state = execute(&runtime, &State::new(), &generate).state;
+
+    let job = Compress::new(
+        "file://b/d".parse().unwrap(),
+        "file://b/c".parse().unwrap(),
+        vec![],
+        Compression::new(Codec::Gzip, None),
+    );
+    let outputs = job.outputs(&state).unwrap();
+    let state = execute(&runtime, &state, &job).state;
+
+    assert_eq!(outputs.len(), 1);
+    for (source, target) in outputs {
+        let before = state.get_object(&source).unwrap().size;
+        let after = state.get_object(&target).unwrap().size;
+        assert!(after < before, "{} -> {}", before, after);
+
+        // Sizes are those of the files rather than of their uncompressed row groups
+        let file = root.join("b/c/p=1/0.parquet");
+        assert_eq!(after.as_usize(), fs::metadata(file).unwrap().len() as usize);
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,96 @@
+use osm::drift::{Drift, DriftError};
+use osm::job::{Job, ReloadDataset, VerifyDataset};
+use osm::memory::MemoryStore;
+use osm::path::DatasetPath;
+use osm::runtime::Runtime;
+use osm::state::State;
+use serde_json::json;
+
+fn runtime(objects: &[(&str, &str)]) -> Runtime {
+    let store = MemoryStore::new();
+    for (path, rows) in objects {
+        store
+            .put_object(
+                &path.parse().unwrap(),
+                format!("id,name\n{}", rows).into_bytes(),
+            )
+            .unwrap();
+    }
+    Runtime::new(Box::new(store), 2)
+}
+
+fn reload(runtime: &Runtime, path: &DatasetPath) -> State {
+    let job = ReloadDataset::new(path.clone());
+    let execution = runtime.execute(&State::new(), job.actions(&State::new()).unwrap());
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution.state
+}
+
+#[test]
+fn missing_extra_and_changed_objects() {
+    let path: DatasetPath = "file://b/d".parse().unwrap();
+    let expected = reload(
+        &runtime(&[
+            ("file://b/d/p=1/0.csv", "1,a\n"),
+            ("file://b/d/p=1/1.csv", "2,b\n"),
+            ("file://b/d/p=2/0.csv", "3,c\n"),
+        ]),
+        &path,
+    );
+    let actual = reload(
+        &runtime(&[
+            ("file://b/d/p=1/0.csv", "1,a\n4,d\n"),
+            ("file://b/d/p=1/2.csv", "2,b\n"),
+            ("file://b/d/p=3/0.csv", "3,c\n"),
+        ]),
+        &path,
+    );
+
+    assert!(Drift::between(&path, &expected, &expected)
+        .unwrap()
+        .is_empty());
+
+    let drift = Drift::between(&path, &expected, &actual).unwrap();
+    assert_eq!(
+        drift.to_json(),
+        json!({
+            "missing_partitions": ["file://b/d/p=2"],
+            "extra_partitions": ["file://b/d/p=3"],
+            "missing_objects": ["file://b/d/p=1/1.csv"],
+            "extra_objects": ["file://b/d/p=1/2.csv"],
+            "changed_objects": [{
+                "object": "file://b/d/p=1/0.csv",
+                "differences": [{"kind": "size", "expected": 12, "actual": 16}],
+            }],
+        })
+    );
+}
+
+#[test]
+fn verify_missing_dataset() {
+    let path: DatasetPath = "file://b/d".parse().unwrap();
+    let state = reload(
+        &runtime(&[
+            ("file://b/d/p=1/0.csv", "1,a\n"),
+            ("file://b/d/p=2/0.csv", "2,b\n"),
+        ]),
+        &path,
+    );
+
+    let runtime = runtime(&[("file://b/e/p=1/0.csv", "1,a\n")]);
+    let job = VerifyDataset::new(path.clone());
+    let execution = runtime.execute(&state, job.actions(&state).unwrap());
+
+    let errors = execution.errors();
+    assert_eq!(errors.len(), 1);
+    match errors[0].downcast_ref::<DriftError>() {
+        Some(DriftError::Drifted(drifted, drift)) => {
+            assert_eq!(drifted, &path);
+            assert_eq!(
+                drift.to_json()["missing_partitions"],
+                json!(["file://b/d/p=1", "file://b/d/p=2"])
+            );
+        }
+        None => panic!("not a drift: {:?}", errors[0]),
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use osm::fault::{Fault, FaultyStore};
+use osm::job::{CopyDataset, Job, ReloadDataset};
+use osm::memory::MemoryStore;
+use osm::path::DatasetPath;
+use osm::retry::RetryPolicy;
+use osm::runtime::{Execution, Runtime};
+use osm::state::State;
+
+fn faulty_runtime(faults: &[&str]) -> Runtime {
+    let store = MemoryStore::new();
+    for partition in &["p=1", "p=2"] {
+        for key in &["0.csv", "1.csv"] {
+            let path = format!("file://b/src/{}/{}", partition, key)
+                .parse()
+                .unwrap();
+            store
+                .put_object(&path, b"id,name\n1,a\n2,b\n".to_vec())
+                .unwrap();
+        }
+    }
+
+    let faults = faults
+        .iter()
+        .map(|fault| fault.parse().unwrap())
+        .collect::<Vec<Fault>>();
+    Runtime::new(Box::new(FaultyStore::new(Box::new(store), faults, 1)), 2)
+}
+
+fn reload(runtime: &Runtime, path: &DatasetPath) -> State {
+    let execution = execute(
+        runtime,
+        &State::new(),
+        &ReloadDataset::new_optional(path.clone()),
+    );
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution.state
+}
+
+fn execute(runtime: &Runtime, state: &State, job: &dyn Job) -> Execution {
+    runtime.execute(state, job.actions(state).unwrap())
+}
+
+fn object_count(state: &State, path: &DatasetPath) -> usize {
+    match state.list_partitions(path) {
+        Ok(partitions) => partitions
+            .iter()
+            .map(|partition| state.list_objects(partition).unwrap().len())
+            .sum(),
+        Err(_) => 0,
+    }
+}
+
+#[test]
+fn rollback_failed_copy() {
+    let runtime = faulty_runtime(&["copy_object,file://b/tgt/p=2/1.csv,fail"]);
+    let source: DatasetPath = "file://b/src".parse().unwrap();
+    let target: DatasetPath = "file://b/tgt".parse().unwrap();
+
+    let state = reload(&runtime, &source);
+    let mut execution = execute(
+        &runtime,
+        &state,
+        &CopyDataset::new(source.clone(), target.clone()),
+    );
+    assert!(execution.has_errors());
+    assert!(object_count(&execution.state, &target) > 0);
+
+    let rollback = runtime.rollback(&mut execution);
+    assert!(!rollback.has_errors(), "{}", rollback.to_json());
+    assert_eq!(object_count(&rollback.state, &target), 0);
+    assert_eq!(object_count(&reload(&runtime, &target), &target), 0);
+    assert_eq!(object_count(&reload(&runtime, &source), &source), 4);
+}
+
+#[test]
+fn retry_transient_failures() {
+    let source: DatasetPath = "file://b/src".parse().unwrap();
+    let target: DatasetPath = "file://b/tgt".parse().unwrap();
+
+    let runtime = faulty_runtime(&["copy_object,*,fail,50%"]);
+    let state = reload(&runtime, &source);
+    let execution = execute(
+        &runtime,
+        &state,
+        &CopyDataset::new(source.clone(), target.clone()),
+    );
+    assert!(execution.has_errors());
+
+    let runtime = faulty_runtime(&["copy_object,*,fail,50%"])
+        .with_retry(RetryPolicy::new(10, Duration::from_millis(1)));
+    let execution = execute(&runtime, &state, &CopyDataset::new(source, target.clone()));
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    assert!(execution
+        .attempts()
+        .iter()
+        .any(|(_, attempts)| *attempts > 1));
+    assert_eq!(object_count(&reload(&runtime, &target), &target), 4);
+}
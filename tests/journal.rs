@@ -0,0 +1,306 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use osm::fault::FaultyStore;
+use osm::generate::{Columns, Generator};
+use osm::job::{CopyDataset, Generate, Job, MovePartition, ReloadDataset, Repartition};
+use osm::journal::{Journal, JournalError};
+use osm::memory::MemoryStore;
+use osm::path::DatasetPath;
+use osm::runtime::{Execution, Runtime};
+use osm::state::State;
+use osm::store::FileStore;
+
+fn journal_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("osm-journal-{}-{}", process::id(), name));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("state"));
+    path
+}
+
+fn runtime() -> Runtime {
+    let store = MemoryStore::new();
+    for (partition, rows) in &[("p=1", "1,a\n2,b\n3,a\n"), ("p=2", "4,\n5,b\n")] {
+        let path = format!("file://b/src/{}/0.csv", partition).parse().unwrap();
+        store
+            .put_object(&path, format!("id,name\n{}", rows).into_bytes())
+            .unwrap();
+    }
+    Runtime::new(Box::new(store), 2)
+}
+
+fn reload(runtime: &Runtime, path: &DatasetPath) -> State {
+    let execution = runtime.execute(
+        &State::new(),
+        ReloadDataset::new(path.clone())
+            .actions(&State::new())
+            .unwrap(),
+    );
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution.state
+}
+
+fn execute(runtime: &Runtime, journal: &mut Journal, state: &State, job: &dyn Job) -> Execution {
+    journal.begin(0, state).unwrap();
+    let actions = job.actions(state).unwrap();
+    journal.plan(actions.action_keys()).unwrap();
+    let execution = runtime.execute_journaled(state, actions, journal);
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution
+}
+
+/// Runs the job, then resumes it from the reopened journal as if the script had been interrupted
+/// right after its last action completed.
+fn resume(name: &str, runtime: &Runtime, state: &State, job: &dyn Job) -> (Execution, Execution) {
+    let path = journal_path(name);
+    let mut journal = Journal::create(path.clone(), name).unwrap();
+    let executed = execute(runtime, &mut journal, state, job);
+    drop(journal);
+
+    let mut journal = Journal::open(path.clone()).unwrap();
+    let resumed_state = journal.resumed_state(0).unwrap().unwrap();
+    let resumed = execute(runtime, &mut journal, &resumed_state, job);
+    journal.finish().unwrap();
+    assert!(!path.exists());
+
+    (executed, resumed)
+}
+
+#[test]
+fn resume_reloads_generated_object() {
+    let runtime = runtime();
+    let generator = Generator::new(
+        "id:int;name:string".parse::<Columns>().unwrap(),
+        "10%".parse().unwrap(),
+        7,
+    );
+    let job = Generate::new("file://b/g/p=1/0.csv".parse().unwrap(), generator, 20);
+
+    let (executed, resumed) = resume("generate", &runtime, &State::new(), &job);
+    assert!(resumed.attempts().is_empty());
+    assert_eq!(
+        resumed.state.to_json().unwrap(),
+        executed.state.to_json().unwrap()
+    );
+
+    let object = resumed
+        .state
+        .get_object(&"file://b/g/p=1/0.csv".parse().unwrap())
+        .unwrap();
+    assert!(object.size.as_usize() > 0);
+}
+
+#[test]
+fn resume_reloads_repartitioned_dataset() {
+    let runtime = runtime();
+    let source: DatasetPath = "file://b/src".parse().unwrap();
+    let target: DatasetPath = "file://b/tgt".parse().unwrap();
+    let state = reload(&runtime, &source);
+    let job = Repartition::new(source, target.clone(), vec!["name".to_string()]);
+
+    let (executed, resumed) = resume("repartition", &runtime, &state, &job);
+    assert!(resumed.attempts().is_empty());
+    assert_eq!(
+        resumed.state.to_json().unwrap(),
+        executed.state.to_json().unwrap()
+    );
+    assert_eq!(resumed.state.list_partitions(&target).unwrap().len(), 3);
+}
+
+#[test]
+fn read_only_journal() {
+    let path = journal_path("read-only");
+    let mut journal = Journal::create(path.clone(), "reload(file://b/src)").unwrap();
+    journal.begin(0, &State::new()).unwrap();
+    drop(journal);
+
+    let mut journal = Journal::open_read_only(path.clone()).unwrap();
+    assert_eq!(journal.source(), "reload(file://b/src)");
+    assert!(journal.resumed_state(0).unwrap().is_some());
+    assert!(journal.end(0).is_err());
+    journal.finish().unwrap();
+    assert!(path.exists());
+
+    Journal::open(path).unwrap().finish().unwrap();
+}
+
+/// Journal of a script whose first statement ended, interrupted while running the second.
+fn interrupted_journal(name: &str) -> PathBuf {
+    let path = journal_path(name);
+    let mut journal = Journal::create(path.clone(), "script").unwrap();
+    journal.begin(0, &State::new()).unwrap();
+    journal.plan(vec!["a".to_string()]).unwrap();
+    journal.start("a").unwrap();
+    journal.complete("a").unwrap();
+    journal.end(0).unwrap();
+
+    journal.begin(1, &State::new()).unwrap();
+    journal
+        .plan(vec!["b".to_string(), "c".to_string(), "d".to_string()])
+        .unwrap();
+    journal.start("b").unwrap();
+    journal.complete("b").unwrap();
+    journal.start("c").unwrap();
+    path
+}
+
+#[test]
+fn replay_journal() {
+    let path = interrupted_journal("replay");
+    let journal = Journal::open(path.clone()).unwrap();
+
+    assert_eq!(journal.source(), "script");
+    assert!(journal.has_ended(0));
+    assert!(!journal.has_ended(1));
+    assert!(journal.resumed_state(0).unwrap().is_none());
+    assert!(journal.resumed_state(1).unwrap().is_some());
+    assert!(journal.is_completed("b"));
+    assert!(!journal.is_interrupted("b"));
+    assert!(journal.is_interrupted("c"));
+    assert!(!journal.is_completed("d") && !journal.is_interrupted("d"));
+    journal.finish().unwrap();
+
+    assert!(!path.exists());
+    assert!(Journal::open(path).is_err());
+}
+
+#[test]
+fn truncated_last_line() {
+    let path = interrupted_journal("truncated");
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(br#"{"event":"completed","ke"#).unwrap();
+
+    // The process died while appending the last line, which is ignored
+    let journal = Journal::open(path.clone()).unwrap();
+    assert!(journal.is_interrupted("c"));
+
+    // A complete line that cannot be read is not from an interrupted append
+    file.write_all(b"\n").unwrap();
+    let error = Journal::open(path.clone()).err().unwrap();
+    assert!(matches!(
+        error.downcast_ref::<JournalError>(),
+        Some(JournalError::Invalid(_, _))
+    ));
+
+    journal.finish().unwrap();
+}
+
+#[test]
+fn plan_mismatch() {
+    let path = interrupted_journal("mismatch");
+    let mut journal = Journal::open(path.clone()).unwrap();
+
+    journal.begin(1, &State::new()).unwrap();
+    let error = journal
+        .plan(vec!["b".to_string(), "c".to_string(), "e".to_string()])
+        .err()
+        .unwrap();
+    assert!(matches!(
+        error.downcast_ref::<JournalError>(),
+        Some(JournalError::Mismatch(1, _))
+    ));
+
+    journal
+        .plan(vec!["d".to_string(), "c".to_string(), "b".to_string()])
+        .unwrap();
+    journal.finish().unwrap();
+}
+
+fn file_store(root: &Path, objects: &[&str]) {
+    for object in objects {
+        let path = root.join(object);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "id,name\n1,a\n").unwrap();
+    }
+}
+
+#[test]
+fn resume_reruns_interrupted_actions() {
+    let root = std::env::temp_dir().join(format!("osm-journal-{}-rerun-root", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    file_store(
+        &root,
+        &["b/src/p=1/0.csv", "b/src/p=1/1.csv", "b/src/p=2/0.csv"],
+    );
+
+    let source: DatasetPath = "file://b/src".parse().unwrap();
+    let target: DatasetPath = "file://b/tgt".parse().unwrap();
+    let job = CopyDataset::new(source.clone(), target.clone());
+    let path = journal_path("rerun");
+
+    // The copy to p=2 fails as if the process died while running it
+    let fault = "copy_object,file://b/tgt/p=2/0.csv,fail".parse().unwrap();
+    let store = FaultyStore::new(Box::new(FileStore::new(root.clone())), vec![fault], 1);
+    let runtime = Runtime::new(Box::new(store), 2);
+    let state = reload(&runtime, &source);
+
+    let mut journal = Journal::create(path.clone(), "script").unwrap();
+    journal.begin(0, &state).unwrap();
+    let actions = job.actions(&state).unwrap();
+    journal.plan(actions.action_keys()).unwrap();
+    assert!(runtime
+        .execute_journaled(&state, actions, &journal)
+        .has_errors());
+    drop(journal);
+
+    let copy = "copy(file://b/src/p=2/0.csv, file://b/tgt/p=2/0.csv)";
+    let mut journal = Journal::open(path.clone()).unwrap();
+    assert!(journal.is_interrupted(copy));
+
+    // Completed copies are not run again, the interrupted one is
+    let runtime = Runtime::new(Box::new(FileStore::new(root.clone())), 2);
+    let state = journal.resumed_state(0).unwrap().unwrap();
+    let execution = execute(&runtime, &mut journal, &state, &job);
+    assert_eq!(execution.attempts(), &[(copy.to_string(), 1)]);
+    assert_eq!(
+        execution.state.to_json().unwrap(),
+        reload(&runtime, &source)
+            .merge(&reload(&runtime, &target))
+            .to_json()
+            .unwrap()
+    );
+
+    journal.finish().unwrap();
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn resume_skips_moves_done_before_interruption() {
+    let root = std::env::temp_dir().join(format!("osm-journal-{}-moved-root", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    file_store(&root, &["b/d/p=1/0.csv"]);
+
+    let dataset: DatasetPath = "file://b/d".parse().unwrap();
+    let job = MovePartition::new(
+        "file://b/d/p=1".parse().unwrap(),
+        "file://b/d/p=2".parse().unwrap(),
+    );
+    let runtime = Runtime::new(Box::new(FileStore::new(root.clone())), 2);
+    let state = reload(&runtime, &dataset);
+
+    // The process died after moving the object, before recording it
+    let path = journal_path("moved");
+    let mut journal = Journal::create(path.clone(), "script").unwrap();
+    journal.begin(0, &state).unwrap();
+    journal
+        .plan(job.actions(&state).unwrap().action_keys())
+        .unwrap();
+    let moved = "move(file://b/d/p=1/0.csv, file://b/d/p=2/0.csv)";
+    journal.start(moved).unwrap();
+    drop(journal);
+    fs::create_dir_all(root.join("b/d/p=2")).unwrap();
+    fs::rename(root.join("b/d/p=1/0.csv"), root.join("b/d/p=2/0.csv")).unwrap();
+
+    let mut journal = Journal::open(path).unwrap();
+    let execution = execute(&runtime, &mut journal, &state, &job);
+    assert!(execution.attempts().iter().all(|(key, _)| key != moved));
+    assert_eq!(
+        execution.state.to_json().unwrap(),
+        reload(&runtime, &dataset).to_json().unwrap()
+    );
+
+    journal.finish().unwrap();
+    fs::remove_dir_all(root).unwrap();
+}
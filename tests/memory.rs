@@ -0,0 +1,111 @@
+use osm::base::Bytes;
+use osm::job::{Job, MovePartition, RebalanceObjects, ReloadDataset};
+use osm::memory::MemoryStore;
+use osm::path::{DatasetPath, PartitionPath};
+use osm::runtime::{Execution, Runtime};
+use osm::state::State;
+
+fn execute(runtime: &Runtime, state: &State, job: &dyn Job) -> Execution {
+    let execution = runtime.execute(state, job.actions(state).unwrap());
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution
+}
+
+fn reload(runtime: &Runtime, path: &DatasetPath) -> State {
+    execute(runtime, &State::new(), &ReloadDataset::new(path.clone())).state
+}
+
+fn object_count(state: &State, path: &PartitionPath) -> usize {
+    state.list_objects(path).unwrap().len()
+}
+
+#[test]
+fn move_partition() {
+    let store = MemoryStore::new();
+    for key in &["0.csv", "1.csv"] {
+        let path = format!("file://b/d/p=1/{}", key).parse().unwrap();
+        store
+            .put_object(&path, b"id,name\n1,a\n2,b\n".to_vec())
+            .unwrap();
+    }
+    let path = "file://b/d/p=2/old.csv".parse().unwrap();
+    store.put_object(&path, b"id,name\n3,c\n".to_vec()).unwrap();
+
+    let runtime = Runtime::new(Box::new(store), 2);
+    let dataset: DatasetPath = "file://b/d".parse().unwrap();
+    let source: PartitionPath = "file://b/d/p=1".parse().unwrap();
+    let target: PartitionPath = "file://b/d/p=2".parse().unwrap();
+
+    let state = reload(&runtime, &dataset);
+    let execution = execute(
+        &runtime,
+        &state,
+        &MovePartition::new(source.clone(), target.clone()),
+    );
+
+    for state in &[execution.state, reload(&runtime, &dataset)] {
+        assert!(!state.contains_partition(&source));
+        assert_eq!(object_count(state, &target), 2);
+        assert!(!state.contains_object(&"file://b/d/p=2/old.csv".parse().unwrap()));
+    }
+}
+
+#[test]
+fn rebalance_objects() {
+    const HEADER: &str = "id,name\n";
+
+    let store = MemoryStore::new();
+    for idx in 0..4 {
+        let rows = (0..50)
+            .map(|row| format!("{},name-{}\n", idx * 50 + row, row))
+            .collect::<String>();
+        let path = format!("file://b/d/p=1/part-{}.csv", idx).parse().unwrap();
+        store
+            .put_object(&path, format!("{}{}", HEADER, rows).into_bytes())
+            .unwrap();
+    }
+
+    let runtime = Runtime::new(Box::new(store), 2);
+    let dataset: DatasetPath = "file://b/d".parse().unwrap();
+    let partition: PartitionPath = "file://b/d/p=1".parse().unwrap();
+
+    // Every object starts with the header, the rest are the rows
+    let rows_size = |state: &State| -> usize {
+        let objects = state.list_objects(&partition).unwrap();
+        let size = state.get_partition(&partition).unwrap().size().as_usize();
+        size - objects.len() * HEADER.len()
+    };
+
+    let state = reload(&runtime, &dataset);
+    let size = rows_size(&state);
+    let target_size = Bytes::new(state.get_partition(&partition).unwrap().size().as_usize() / 2);
+    let execution = execute(
+        &runtime,
+        &state,
+        &RebalanceObjects::new(partition.clone(), target_size),
+    );
+
+    for state in &[execution.state, reload(&runtime, &dataset)] {
+        assert_eq!(object_count(state, &partition), 2);
+        assert_eq!(rows_size(state), size);
+    }
+}
+
+#[test]
+fn plan_reload() {
+    let store = MemoryStore::new();
+    let path = "file://b/d/p=1/0.csv".parse().unwrap();
+    store.put_object(&path, b"id,name\n1,a\n".to_vec()).unwrap();
+
+    let runtime = Runtime::new(Box::new(store), 2);
+    let dataset: DatasetPath = "file://b/d".parse().unwrap();
+    let job = ReloadDataset::new(dataset.clone());
+    let plan = runtime
+        .plan(&State::new(), job.actions(&State::new()).unwrap())
+        .unwrap();
+
+    assert_eq!(
+        plan.state.to_json().unwrap(),
+        reload(&runtime, &dataset).to_json().unwrap()
+    );
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::process;
+use std::sync::Arc;
+
+use arrow::array::{Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+
+use osm::base::ToStdPath;
+use osm::job::{Job, ReloadDataset, Repartition};
+use osm::path::DatasetPath;
+use osm::repartition::{Partitioner, RepartitionError};
+use osm::runtime::Runtime;
+use osm::state::State;
+use osm::store::FileStore;
+
+fn batch() -> RecordBatch {
+    let schema = Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("group", DataType::Utf8, true),
+    ]);
+    let ids = Int32Array::from(vec![1, 2, 3, 4, 5, 6]);
+    let names = StringArray::from(vec![
+        Some("a"),
+        Some("b"),
+        Some("a"),
+        Some(""),
+        None,
+        Some("a/b"),
+    ]);
+    let groups = StringArray::from(vec![
+        Some("x"),
+        Some("x"),
+        Some("y"),
+        Some("x"),
+        Some("x"),
+        None,
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![Arc::new(ids), Arc::new(names), Arc::new(groups)],
+    )
+    .unwrap()
+}
+
+fn ids(batch: &RecordBatch) -> Vec<i32> {
+    let column = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .unwrap();
+    (0..column.len()).map(|row| column.value(row)).collect()
+}
+
+#[test]
+fn split_by_keys() {
+    let partitioner = Partitioner::new(vec!["name".to_string(), "group".to_string()]);
+    let split = partitioner
+        .split(&batch())
+        .unwrap()
+        .iter()
+        .map(|(partition, batch)| (partition.to_string(), ids(batch), batch.num_columns()))
+        .collect::<Vec<(String, Vec<i32>, usize)>>();
+
+    // Empty strings, nulls and separators cannot appear as such in a partition
+    assert_eq!(
+        split,
+        vec![
+            ("name=__empty__/group=x".to_string(), vec![4], 3),
+            ("name=__null__/group=x".to_string(), vec![5], 3),
+            ("name=a/group=x".to_string(), vec![1], 3),
+            ("name=a/group=y".to_string(), vec![3], 3),
+            ("name=a-b/group=__null__".to_string(), vec![6], 3),
+            ("name=b/group=x".to_string(), vec![2], 3),
+        ]
+    );
+
+    let error = Partitioner::new(vec!["missing".to_string()])
+        .split(&batch())
+        .err()
+        .unwrap();
+    assert!(matches!(
+        error.downcast_ref::<RepartitionError>(),
+        Some(RepartitionError::MissingColumn(column)) if column == "missing"
+    ));
+}
+
+#[test]
+fn repartition_dataset() {
+    let root = std::env::temp_dir().join(format!("osm-repartition-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for (partition, rows) in &[("p=1", "1,a\n2,b\n3,a\n"), ("p=2", "4,\n5,b\n")] {
+        let path = root.join(format!("b/src/{}/0.csv", partition));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("id,name\n{}", rows)).unwrap();
+    }
+    let runtime = Runtime::new(Box::new(FileStore::new(root.clone())), 2);
+
+    let source: DatasetPath = "file://b/src".parse().unwrap();
+    let target: DatasetPath = "file://b/tgt".parse().unwrap();
+    let reload = ReloadDataset::new(source.clone());
+    let state = runtime
+        .execute(&State::new(), reload.actions(&State::new()).unwrap())
+        .state;
+
+    let job = Repartition::new(source, target.clone(), vec!["name".to_string()]);
+    let execution = runtime.execute(&state, job.actions(&state).unwrap());
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+
+    // Rows of every partition, from all of its objects
+    let mut partitions = vec![];
+    for partition in execution.state.list_partitions(&target).unwrap() {
+        let mut rows = vec![];
+        for object in execution.state.list_objects(&partition).unwrap() {
+            let contents = fs::read_to_string(root.join(object.std_path())).unwrap();
+            rows.extend(contents.lines().skip(1).map(|row| row.to_string()));
+        }
+        rows.sort();
+        partitions.push((partition.partition.to_string(), rows));
+    }
+    assert_eq!(
+        partitions,
+        vec![
+            ("name=__empty__".to_string(), vec!["4,".to_string()]),
+            (
+                "name=a".to_string(),
+                vec!["1,a".to_string(), "3,a".to_string()]
+            ),
+            (
+                "name=b".to_string(),
+                vec!["2,b".to_string(), "5,b".to_string()]
+            ),
+        ]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use osm::base::{Protocol, ToStdPath};
+use osm::path::ObjectPath;
+use osm::repartition::Partitioner;
+use osm::router::{RouterError, StoreRouter};
+use osm::store::{FileStore, Store};
+
+const CONTENT: &[u8] = b"id,name\n1,a\n2,b\n";
+
+fn root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("osm-router-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+fn object(path: &str) -> ObjectPath {
+    path.parse().unwrap()
+}
+
+fn write(router: &StoreRouter, path: &str) {
+    router
+        .write_object(&object(path), &mut &CONTENT[..])
+        .unwrap();
+}
+
+#[test]
+fn bucket_over_protocol() {
+    let (protocol_root, bucket_root) = (root("protocol"), root("bucket"));
+    let router = StoreRouter::new()
+        .route_bucket(
+            "file://b".parse().unwrap(),
+            Box::new(FileStore::new(bucket_root.clone())),
+        )
+        .route_protocol(
+            Protocol::File,
+            Box::new(FileStore::new(protocol_root.clone())),
+        );
+
+    write(&router, "file://a/d/p=1/0.csv");
+    write(&router, "file://b/d/p=1/0.csv");
+    assert!(protocol_root.join("a/d/p=1/0.csv").exists());
+    assert!(!protocol_root.join("b").exists());
+    assert!(bucket_root.join("b/d/p=1/0.csv").exists());
+    assert!(!bucket_root.join("a").exists());
+
+    let error = router
+        .list_partitions(&"s3://b/d".parse().unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(
+        error.downcast_ref::<RouterError>(),
+        Some(RouterError::MissingStore(bucket)) if bucket.to_string() == "s3://b"
+    ));
+
+    fs::remove_dir_all(protocol_root).unwrap();
+    fs::remove_dir_all(bucket_root).unwrap();
+}
+
+#[test]
+fn replace_routes() {
+    let roots = (root("replaced"), root("first"), root("second"));
+    let (replaced, first, second) = &roots;
+    let router = StoreRouter::new()
+        .route_protocol(Protocol::File, Box::new(FileStore::new(replaced.clone())))
+        .route_bucket(
+            "file://b".parse().unwrap(),
+            Box::new(FileStore::new(replaced.clone())),
+        )
+        .route_protocol(Protocol::File, Box::new(FileStore::new(first.clone())))
+        .route_bucket(
+            "file://b".parse().unwrap(),
+            Box::new(FileStore::new(second.clone())),
+        );
+
+    // Replacing the store of a route leaves the other routes alone
+    write(&router, "file://a/d/p=1/0.csv");
+    write(&router, "file://b/d/p=1/0.csv");
+    assert!(fs::read_dir(replaced).unwrap().next().is_none());
+    assert!(first.join("a/d/p=1/0.csv").exists());
+    assert!(second.join("b/d/p=1/0.csv").exists());
+
+    for root in &[replaced, first, second] {
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[test]
+fn transfer_between_stores() {
+    let (source_root, target_root) = (root("source"), root("target"));
+    let router = StoreRouter::new()
+        .route_protocol(
+            Protocol::File,
+            Box::new(FileStore::new(source_root.clone())),
+        )
+        .route_bucket(
+            "file://t".parse().unwrap(),
+            Box::new(FileStore::new(target_root.clone())),
+        );
+    write(&router, "file://s/d/p=1/0.csv");
+    write(&router, "file://s/d/p=1/1.csv");
+
+    // Copies and moves go through the content of objects
+    router
+        .copy_object(
+            &object("file://s/d/p=1/0.csv"),
+            &object("file://t/d/p=1/0.csv"),
+        )
+        .unwrap();
+    router
+        .move_object(
+            &object("file://s/d/p=1/1.csv"),
+            &object("file://t/d/p=1/1.csv"),
+        )
+        .unwrap();
+    assert!(source_root.join("s/d/p=1/0.csv").exists());
+    assert!(!source_root.join("s/d/p=1/1.csv").exists());
+    for key in &["0.csv", "1.csv"] {
+        let path = target_root.join("t/d/p=1").join(key);
+        assert_eq!(fs::read(path).unwrap(), CONTENT);
+    }
+
+    // Rewrites run on a staged copy of their inputs, and only write their outputs to the target
+    let state = router
+        .convert_object(
+            &object("file://s/d/p=1/0.csv"),
+            &object("file://t/c/p=1/0.parquet"),
+        )
+        .unwrap();
+    assert_eq!(state.num_rows(), Some(2));
+    assert!(target_root.join("t/c/p=1/0.parquet").exists());
+    assert!(!target_root.join("s").exists());
+    assert!(!source_root.join("t").exists());
+
+    let outputs = router
+        .repartition_objects(
+            &[object("file://s/d/p=1/0.csv")],
+            &"file://t/r".parse().unwrap(),
+            &Partitioner::new(vec!["name".to_string()]),
+        )
+        .unwrap();
+    assert_eq!(outputs.len(), 2);
+    for (path, _) in outputs {
+        assert!(target_root.join(path.std_path()).exists(), "{}", path);
+    }
+    assert!(!target_root.join("s").exists());
+
+    fs::remove_dir_all(source_root).unwrap();
+    fs::remove_dir_all(target_root).unwrap();
+}
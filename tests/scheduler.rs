@@ -0,0 +1,194 @@
+use osm::action::{
+    Action, ActionTree, Actions, CopyAction, Keys, MoveAction, RebalanceAction,
+    RemoveDatasetAction, RemoveObjectAction, RemovePartitionAction,
+};
+use osm::base::Bytes;
+use osm::effect::{self, Effect, EffectIndex};
+use osm::path::{DatasetPath, ObjectPath, PartitionPath};
+
+fn object(path: &str) -> ObjectPath {
+    path.parse().unwrap()
+}
+
+fn partition(path: &str) -> PartitionPath {
+    path.parse().unwrap()
+}
+
+fn dataset(path: &str) -> DatasetPath {
+    path.parse().unwrap()
+}
+
+/// Keys of the actions of every layer, in the order the layers run.
+fn layers(tree: &ActionTree) -> Vec<Vec<String>> {
+    let mut layers = vec![];
+    let mut completed = Keys::new();
+
+    while completed.len() != tree.size() {
+        let batch = tree.next_batch(&completed);
+        let mut keys = batch
+            .iter()
+            .flat_map(|(_, actions)| actions.iter().map(|action| action.key()))
+            .collect::<Vec<String>>();
+        keys.sort();
+
+        layers.push(keys);
+        completed.extend(batch.iter().map(|(key, _)| *key));
+    }
+
+    layers
+}
+
+#[test]
+fn containment_conflicts() {
+    let mut index = EffectIndex::default();
+    index.record(&[Effect::create(object("file://b/d/p=1/0.csv"))], 0);
+    index.record(&[Effect::read(partition("file://b/d/p=2"))], 1);
+
+    // Reads conflict with writes of what they contain or what contains them
+    assert_eq!(
+        index.conflict(&Effect::read(object("file://b/d/p=1/0.csv"))),
+        Some(0)
+    );
+    assert_eq!(
+        index.conflict(&Effect::read(partition("file://b/d/p=1"))),
+        Some(0)
+    );
+    assert_eq!(
+        index.conflict(&Effect::read(dataset("file://b/d"))),
+        Some(0)
+    );
+    assert_eq!(
+        index.conflict(&Effect::read(object("file://b/d/p=1/1.csv"))),
+        None
+    );
+    assert_eq!(index.conflict(&Effect::read(dataset("file://b/e"))), None);
+
+    // Writes also conflict with reads
+    assert_eq!(
+        index.conflict(&Effect::remove(object("file://b/d/p=2/0.csv"))),
+        Some(1)
+    );
+    assert_eq!(
+        index.conflict(&Effect::remove(dataset("file://b/d"))),
+        Some(1)
+    );
+    assert_eq!(
+        index.conflict(&Effect::create(object("file://b/d/p=1/1.csv"))),
+        None
+    );
+    assert_eq!(
+        index.conflict(&Effect::create(partition("file://b/d/p=3"))),
+        None
+    );
+
+    let effects = [
+        Effect::read(object("file://b/d/p=1/0.csv")),
+        Effect::create(object("file://b/d/p=2/1.csv")),
+    ];
+    assert_eq!(index.layer(&effects), 2);
+    assert_eq!(index.layer(&[Effect::read(partition("file://b/d/p=3"))]), 0);
+}
+
+#[test]
+fn concurrent_conflicts() {
+    let copies: Actions = vec![
+        Box::new(CopyAction::new(
+            object("file://b/d/p=1/0.csv"),
+            object("file://b/e/p=1/0.csv"),
+        )),
+        Box::new(CopyAction::new(
+            object("file://b/d/p=1/1.csv"),
+            object("file://b/e/p=1/1.csv"),
+        )),
+    ];
+    let actions = copies
+        .iter()
+        .map(|a| a.as_ref())
+        .collect::<Vec<&dyn Action>>();
+    assert!(effect::check_conflicts(&actions).is_ok());
+
+    let removals: Actions = vec![
+        Box::new(RemoveObjectAction::new(object("file://b/e/p=1/0.csv"))),
+        Box::new(RemoveDatasetAction::new(dataset("file://b/e"))),
+    ];
+    let actions = removals
+        .iter()
+        .map(|a| a.as_ref())
+        .collect::<Vec<&dyn Action>>();
+    assert!(effect::check_conflicts(&actions).is_err());
+}
+
+#[test]
+fn layers_follow_containment() {
+    let actions: Actions = vec![
+        Box::new(RemoveObjectAction::new(object("file://b/d/p=1/0.csv"))),
+        Box::new(RemoveObjectAction::new(object("file://b/d/p=2/0.csv"))),
+        Box::new(RemovePartitionAction::new(partition("file://b/d/p=1"))),
+        Box::new(RemovePartitionAction::new(partition("file://b/d/p=2"))),
+        Box::new(RemoveDatasetAction::new(dataset("file://b/d"))),
+        Box::new(CopyAction::new(
+            object("file://b/e/p=1/0.csv"),
+            object("file://b/f/p=1/0.csv"),
+        )),
+        Box::new(RemoveObjectAction::new(object("file://b/d/p=3/0.csv"))),
+    ];
+    let tree = ActionTree::from_effects(actions);
+
+    assert_eq!(
+        layers(&tree),
+        vec![
+            vec![
+                "copy(file://b/e/p=1/0.csv, file://b/f/p=1/0.csv)",
+                "remove(file://b/d/p=1/0.csv)",
+                "remove(file://b/d/p=2/0.csv)",
+            ],
+            // Removing the dataset waits for the partitions, the last removal for the dataset
+            vec!["rm(file://b/d/p=1/)", "rm(file://b/d/p=2/)"],
+            vec!["rm(file://b/d/)"],
+            vec!["remove(file://b/d/p=3/0.csv)"],
+        ]
+    );
+}
+
+#[test]
+fn split_moves_before_dependents() {
+    let actions: Actions = vec![
+        Box::new(MoveAction::new(
+            object("file://b/d/p=1/0.csv"),
+            object("file://b/e/p=1/0.csv"),
+        )),
+        Box::new(CopyAction::new(
+            object("file://b/d/p=2/0.csv"),
+            object("file://b/e/p=2/0.csv"),
+        )),
+        Box::new(RemovePartitionAction::new(partition("file://b/d/p=1"))),
+    ];
+    let tree = ActionTree::from_effects(actions).split_moves();
+
+    assert_eq!(
+        layers(&tree),
+        vec![
+            vec![
+                "copy(file://b/d/p=1/0.csv, file://b/e/p=1/0.csv)",
+                "copy(file://b/d/p=2/0.csv, file://b/e/p=2/0.csv)",
+            ],
+            vec!["remove(file://b/d/p=1/0.csv)"],
+            vec!["rm(file://b/d/p=1/)"],
+        ]
+    );
+}
+
+#[test]
+fn rebalance_formats() {
+    let rebalance = |paths: &[&str]| {
+        let paths = paths.iter().map(|path| object(path)).collect();
+        RebalanceAction::new(paths, Bytes::new(10), 2).map(|action| action.effects().len())
+    };
+
+    assert_eq!(
+        rebalance(&["file://b/d/p=1/a.csv", "file://b/d/p=1/b.csv"]).unwrap(),
+        4
+    );
+    assert!(rebalance(&["file://b/d/p=1/a.csv", "file://b/d/p=1/b.parquet"]).is_err());
+    assert!(rebalance(&["file://b/d/p=1/a.txt"]).is_err());
+}
@@ -0,0 +1,99 @@
+use osm::script::{Operation, Script};
+
+const SCRIPT: &str = "
+reload(file://b/d)
+verify(s3://b/d)  # checks the store
+move-partition(file://b/d/p=1, file://b/d/p=2) copy-partition(file://b/d/p=2, file://b/e/p=2)
+copy-dataset(file://b/d, file://b/e)
+move-dataset(file://b/e, file://b/f)
+remove-dataset(file://b/f)
+rebalance(file://b/d/p=1)
+rebalance(file://b/d/p=1/q=a, 2MiB)
+sample(file://b/d, file://b/s, 10%)
+sample(file://b/d, file://b/s, 5%, 42, p=1, p=2/q=a)
+repartition(file://b/d, file://b/r, id, name)
+compress(file://b/d, file://b/c, zstd)
+compress(file://b/d, file://b/c, gzip, 6, p=1)
+generate(file://b/g/p=1/0.parquet, id:int;name:string, 100)
+generate(file://b/g/p=1/0.csv, id:int, 10, 20%, 7)
+convert(file://b/d, file://b/pq, parquet)
+ls(file://b/d)
+ls(file://b/d, true)
+ls-objects(file://b/d/p=1)
+size-of(file://b/d)
+size-of(file://b/d, true, p=1)
+";
+
+fn operations(script: &Script) -> Vec<String> {
+    script
+        .statements
+        .iter()
+        .map(|statement| statement.operation.to_string())
+        .collect()
+}
+
+#[test]
+fn display_parses_back() {
+    let script: Script = SCRIPT.parse().unwrap();
+    assert_eq!(script.statements.len(), 22);
+
+    let displayed = operations(&script);
+    let reparsed: Script = displayed.join("\n").parse().unwrap();
+    assert_eq!(operations(&reparsed), displayed);
+}
+
+#[test]
+fn compress_parses_back() {
+    for source in &[
+        "compress(file://b/d, file://b/c, zstd)",
+        "compress(file://b/d, file://b/c, gzip, 6)",
+        "compress(file://b/d, file://b/d, snappy, p=1, p=2/q=a)",
+        "compress(file://b/d, file://b/c, brotli, 0, p=1)",
+    ] {
+        let script: Script = source.parse().unwrap();
+        let operation = &script.statements[0].operation;
+        let reparsed: Script = operation.to_string().parse().unwrap();
+        assert_eq!(&reparsed.statements[0].operation, operation);
+    }
+}
+
+#[test]
+fn sample_seed_or_partitions() {
+    let parse = |source: &str| {
+        let mut script: Script = source.parse().unwrap();
+        script.statements.remove(0).operation
+    };
+    let sample = |seed, partitions: &[&str]| {
+        Operation::Sample(
+            "file://b/d".parse().unwrap(),
+            "file://b/s".parse().unwrap(),
+            partitions.iter().map(|p| p.parse().unwrap()).collect(),
+            "10%".parse().unwrap(),
+            seed,
+        )
+    };
+
+    assert_eq!(
+        parse("sample(file://b/d, file://b/s, 10%, p=1)"),
+        sample(0, &["p=1"])
+    );
+    assert_eq!(
+        parse("sample(file://b/d, file://b/s, 10%, 42, p=1, p=2)"),
+        sample(42, &["p=1", "p=2"])
+    );
+    assert_eq!(
+        parse("sample(file://b/d, file://b/s, 10%, 42)"),
+        sample(42, &[])
+    );
+}
+
+#[test]
+fn invalid_statements() {
+    for script in &[
+        "reload(file://b/d",
+        "unknown(file://b/d)",
+        "move-partition(file://b/d/p=1)",
+    ] {
+        assert!(script.parse::<Script>().is_err(), "{}", script);
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::process;
+
+use osm::generate::Generator;
+use osm::job::{Generate, Job, ReloadDataset};
+use osm::memory::MemoryStore;
+use osm::runtime::{Execution, Runtime};
+use osm::snapshot::Snapshot;
+use osm::state::State;
+
+fn execute(runtime: &Runtime, state: &State, job: &dyn Job) -> Execution {
+    let execution = runtime.execute(state, job.actions(state).unwrap());
+    assert!(!execution.has_errors(), "{}", execution.to_json());
+    execution
+}
+
+/// State of a dataset with a CSV and a Parquet partition, reloaded from the store.
+fn state() -> State {
+    let runtime = Runtime::new(Box::new(MemoryStore::new()), 2);
+    let mut state = State::new();
+
+    for (object, seed) in &[("file://b/d/p=1/0.csv", 1), ("file://b/d/p=2/0.parquet", 2)] {
+        let generator = Generator::new(
+            "id:int;name:string;score:float".parse().unwrap(),
+            "10%".parse().unwrap(),
+            *seed,
+        );
+        let job = Generate::new(object.parse().unwrap(), generator, 20);
+        state = execute(&runtime, &state, &job).state;
+    }
+
+    let job = ReloadDataset::new("file://b/d".parse().unwrap());
+    execute(&runtime, &State::new(), &job).state
+}
+
+fn snapshot(name: &str) -> Snapshot {
+    let path = std::env::temp_dir().join(format!("osm-snapshot-{}-{}", process::id(), name));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("stale"));
+    Snapshot::new(path)
+}
+
+#[test]
+fn save_and_load() {
+    let snapshot = snapshot("round-trip");
+    assert!(snapshot.load().unwrap().is_none());
+
+    let state = state();
+    snapshot.save(&state).unwrap();
+    let loaded = snapshot.load().unwrap().unwrap();
+    assert_eq!(loaded.to_json().unwrap(), state.to_json().unwrap());
+
+    let partitions = loaded
+        .list_partitions(&"file://b/d".parse().unwrap())
+        .unwrap();
+    assert_eq!(partitions.len(), 2);
+    let parquet = loaded
+        .get_object(&"file://b/d/p=2/0.parquet".parse().unwrap())
+        .unwrap();
+    assert_eq!(parquet.num_rows(), Some(20));
+}
+
+#[test]
+fn stale_snapshot() {
+    let snapshot = snapshot("stale");
+
+    // Nothing to mark before the first save
+    snapshot.mark_stale().unwrap();
+    assert!(!snapshot.is_stale());
+
+    let state = state();
+    snapshot.save(&state).unwrap();
+    snapshot.mark_stale().unwrap();
+    assert!(snapshot.is_stale());
+    assert!(snapshot.load().unwrap().is_none());
+
+    // Saving again clears the marker
+    snapshot.save(&state).unwrap();
+    assert!(!snapshot.is_stale());
+    assert!(snapshot.load().unwrap().is_some());
+}
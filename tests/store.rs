@@ -0,0 +1,127 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use osm::base::Bytes;
+use osm::path::{DatasetPath, ObjectPath};
+use osm::store::{FileStore, RebalanceTarget, Store, StoreError};
+
+fn root(name: &str, objects: &[&str]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("osm-store-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&root);
+    for object in objects {
+        let path = root.join(object);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "id,name\n1,a\n2,b\n").unwrap();
+    }
+    root
+}
+
+fn partitions(store: &FileStore, dataset: &DatasetPath) -> Vec<String> {
+    let mut partitions = store
+        .list_partitions(dataset)
+        .unwrap()
+        .iter()
+        .map(|partition| partition.to_string())
+        .collect::<Vec<String>>();
+    partitions.sort();
+    partitions
+}
+
+#[test]
+fn list_nested_partitions() {
+    let root = root(
+        "nested",
+        &[
+            "b/d/p=1/q=a/0.csv",
+            "b/d/p=1/q=b/0.csv",
+            "b/d/p=1/q=b/r=x/0.csv",
+            "b/d/p=2/0.csv",
+        ],
+    );
+    let store = FileStore::new(root.clone());
+    let dataset: DatasetPath = "file://b/d".parse().unwrap();
+
+    // Objects next to sub-partitions are not partitions of their own
+    assert_eq!(
+        partitions(&store, &dataset),
+        vec!["p=1/q=a", "p=1/q=b/r=x", "p=2"]
+    );
+
+    fs::create_dir_all(root.join("b/d/p=3/empty")).unwrap();
+    let error = store.list_partitions(&dataset).err().unwrap();
+    assert!(matches!(
+        error.downcast_ref::<StoreError>(),
+        Some(StoreError::InvalidPartition(name)) if name == "p=3/empty"
+    ));
+    fs::remove_dir(root.join("b/d/p=3/empty")).unwrap();
+
+    fs::write(root.join("b/d/0.csv"), "id\n").unwrap();
+    let error = store.list_partitions(&dataset).err().unwrap();
+    assert!(matches!(
+        error.downcast_ref::<StoreError>(),
+        Some(StoreError::InvalidPartition(name)) if name == "0.csv"
+    ));
+
+    let error = store
+        .list_partitions(&"file://b/e".parse().unwrap())
+        .err()
+        .unwrap();
+    assert!(matches!(
+        error.downcast_ref::<StoreError>(),
+        Some(StoreError::MissingDataset(_))
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn remove_nested_partitions() {
+    let root = root("remove", &["b/d/p=1/q=a/0.csv", "b/d/p=1/q=b/0.csv"]);
+    let store = FileStore::new(root.clone());
+    let dataset: DatasetPath = "file://b/d".parse().unwrap();
+    let remove = |partition: &str| {
+        let object = format!("file://b/d/{}/0.csv", partition).parse().unwrap();
+        store.remove_object(&object).unwrap();
+        store
+            .remove_partition(&format!("file://b/d/{}", partition).parse().unwrap())
+            .unwrap();
+    };
+
+    // Parents are kept while they hold other partitions
+    remove("p=1/q=a");
+    assert!(!root.join("b/d/p=1/q=a").exists());
+    assert_eq!(partitions(&store, &dataset), vec!["p=1/q=b"]);
+
+    // Then removed along with the last one, but not the dataset
+    remove("p=1/q=b");
+    assert!(!root.join("b/d/p=1").exists());
+    assert!(root.join("b/d").is_dir());
+    assert!(partitions(&store, &dataset).is_empty());
+
+    store.remove_dataset(&dataset).unwrap();
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn rebalance_by_size() {
+    let root = root("rebalance", &["b/d/p=1/0.csv", "b/d/p=1/1.csv"]);
+    let store = FileStore::new(root.clone());
+    let object = |key: &str| -> ObjectPath { format!("file://b/d/p=1/{}", key).parse().unwrap() };
+
+    let states = store
+        .rebalance_objects(
+            &[object("0.csv"), object("1.csv")],
+            &[object("2.csv")],
+            &RebalanceTarget::Size(Bytes::new(1024)),
+        )
+        .unwrap();
+
+    assert_eq!(states.len(), 1);
+    assert_eq!(
+        states[0].size.as_usize(),
+        fs::metadata(root.join("b/d/p=1/2.csv")).unwrap().len() as usize
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}